@@ -1,8 +1,24 @@
-use defmt::info;
-use driver::{self, Fan, Mcp9808};
+use defmt::{info, warn};
+use driver::{self, fan::Speed, Fan, Mcp9808};
 use embassy_rp::{i2c, pwm};
-use fan_controller::fan_curve::{self, FanCurve};
-use uom::si::{frequency::hertz, ratio::percent, thermodynamic_temperature::degree_celsius};
+use embassy_time::{Duration, Instant, Timer};
+use fan_controller::{
+    aggregate::{self, Aggregate, MAX_SOURCES},
+    calibration::{self, Calibration, MAX_CALIBRATION_POINTS},
+    closed_loop::{self, RpmRegulator},
+    control::{self, Input, Strategy},
+    filter::Filter,
+    health::{self, FaultDetector, Status},
+    hysteresis::Hysteresis,
+    ramp::{self, Ramp},
+    units::{AngularVelocity, ThermodynamicTemperature, Time},
+    zero_rpm::{self, ZeroRpm},
+};
+use heapless::Vec;
+use uom::si::{
+    angular_velocity::revolution_per_minute, ratio::percent,
+    thermodynamic_temperature::degree_celsius, time::microsecond,
+};
 
 type Result<T> = core::result::Result<T, Error>;
 
@@ -15,34 +31,312 @@ pub enum Error {
     /// A fan decode error occurred.
     #[error("fan driver error: {0}")]
     FanDecodeError(#[from] driver::fan::Error),
-    /// A fan curve error occurred.
-    #[error("fan curve error: {0}")]
-    FanCurveError(#[from] fan_curve::Error),
+    /// A fan calibration error occurred.
+    #[error("calibration error: {0}")]
+    CalibrationError(#[from] calibration::Error),
+    /// A fan control strategy error occurred.
+    #[error("fan control error: {0}")]
+    ControlError(#[from] control::Error),
+    /// A ramp limiter error occurred.
+    #[error("ramp error: {0}")]
+    RampError(#[from] ramp::Error),
+    /// An RPM regulator error occurred.
+    #[error("rpm regulator error: {0}")]
+    RegulatorError(#[from] closed_loop::Error),
+    /// A fan fault was detected.
+    #[error("fan health error: {0}")]
+    HealthError(#[from] health::Error),
+    /// A zero RPM policy error occurred.
+    #[error("zero rpm error: {0}")]
+    ZeroRpmError(#[from] zero_rpm::Error),
 }
 
 #[derive(derive_builder::Builder)]
 #[builder(no_std, pattern = "owned")]
-pub struct FanControl<'a, C: pwm::Channel, S: i2c::Instance> {
+pub struct FanControl<'a, C: pwm::Channel, S: i2c::Instance + 'static, F: Filter> {
     fan: Fan<'a, C>,
-    sensor: Mcp9808<'a, S>,
+    sensors: Vec<Mcp9808<'a, S>, MAX_SOURCES>,
     #[builder(default)]
-    curve: FanCurve,
+    aggregate: Aggregate,
+    filter: F,
+    /// Ambient sensor for differential strategies.
+    #[builder(default)]
+    ambient_sensor: Option<Mcp9808<'a, S>>,
+    #[builder(default)]
+    strategy: Strategy,
+    #[builder(default)]
+    hysteresis: Hysteresis,
+    #[builder(default)]
+    ramp: Ramp,
+    /// Regulator for closed-loop RPM targeting. Without one, fan speeds are applied as duty.
+    #[builder(default)]
+    regulator: Option<RpmRegulator>,
+    /// Measured duty to RPM mapping. With one, open-loop fan speeds are read as a percentage of
+    /// the fan's max RPM rather than as duty.
+    #[builder(default)]
+    calibration: Option<Calibration>,
+    #[builder(default)]
+    zero_rpm: ZeroRpm,
+    /// Detector of stalled, missing, or degraded fans.
+    #[builder(default)]
+    fault_detector: Option<FaultDetector>,
+    #[builder(setter(skip))]
+    fan_speed: Option<Speed>,
+    /// Whether a sensor reported its critical temperature limit on the last update. Only sensors
+    /// whose `T_CRIT` has been set report it.
+    #[builder(setter(skip))]
+    critical: bool,
 }
 
-impl<'a, C: pwm::Channel, S: i2c::Instance> FanControl<'a, C, S> {
+impl<'a, C: pwm::Channel, S: i2c::Instance + 'static, F: Filter> FanControl<'a, C, S, F> {
     #[must_use]
-    pub fn builder() -> FanControlBuilder<'a, C, S> {
+    pub fn builder() -> FanControlBuilder<'a, C, S, F> {
         FanControlBuilder::default()
     }
 
     pub async fn update(&mut self) -> Result<()> {
-        let temp = self.sensor.temp().await?;
-        info!("temp: {}°C", temp.get::<degree_celsius>());
-        let target_speed = self.curve.sample(temp)?;
-        info!("new fan speed: {}%", target_speed.get::<percent>());
-        // let current_freq = self.fan.fan_freq().await?;
-        // info!("current fan freq: {}Hz", current_freq.get::<hertz>());
-        self.fan.set_fan_speed(&target_speed);
+        #[allow(clippy::cast_precision_loss)]
+        let now = Time::new::<microsecond>(Instant::now().as_micros() as f64);
+
+        let input = Input {
+            temp: self
+                .temp(now)
+                .await
+                .map(|temp| self.filter.update(now, temp)),
+            ambient: self.ambient_temp(now).await,
+        };
+        if let Some(temp) = input.temp {
+            info!("temp: {}°C", temp.get::<degree_celsius>());
+        }
+        let target_speed = match self.strategy.update(now, input) {
+            Ok(target_speed) => target_speed,
+            Err(e) => {
+                // Without a usable temperature the fan can't be controlled, so fail safe.
+                warn!("no usable temperature, running fan at full speed");
+                self.set_fan_speed(Speed::full())?;
+                // Ramp back down from full speed once a temperature is usable again.
+                self.ramp.reset_to(now, Speed::full());
+                return Err(e.into());
+            }
+        };
+        let target_speed = if let Some(key) = self.strategy.key(input) {
+            self.hysteresis.update(key, target_speed);
+            self.hysteresis.fan_speed().unwrap_or(target_speed)
+        } else {
+            self.hysteresis.reset();
+            target_speed
+        };
+
+        self.ramp.update(now, target_speed)?;
+        let target_speed = self.ramp.fan_speed().unwrap_or(target_speed);
+
+        let rpm = if self.regulator.is_some() || self.fault_detector.is_some() {
+            self.rpm().await
+        } else {
+            None
+        };
+        // The measured speed reflects the duty applied by the previous update.
+        if let (Some(detector), Some(duty)) = (&mut self.fault_detector, self.fan_speed) {
+            detector.update(now, duty, rpm);
+        }
+
+        let target_speed = match &mut self.regulator {
+            Some(regulator) => {
+                info!(
+                    "target: {}rpm",
+                    regulator
+                        .target_rpm(target_speed)
+                        .get::<revolution_per_minute>()
+                );
+                regulator.update(now, target_speed, rpm)?
+            }
+            None => match &self.calibration {
+                Some(calibration) => calibration.duty(target_speed),
+                None => target_speed,
+            },
+        };
+
+        let fan_speed = self.zero_rpm.update(now, input.temp, target_speed)?;
+        // The sensor's own critical limit overrides the control stages.
+        let fan_speed = if self.critical {
+            Speed::full()
+        } else {
+            fan_speed
+        };
+        if self.zero_rpm.is_stopped() || self.zero_rpm.is_kicking() {
+            // The regulator isn't in control, so don't let it chase the RPM.
+            if let Some(regulator) = &mut self.regulator {
+                regulator.reset();
+            }
+        }
+        self.set_fan_speed(fan_speed)?;
+
+        // Faults are raised once the new fan speed is applied, so the fan is still controlled.
+        if let Some(detector) = &self.fault_detector {
+            detector.check()?;
+        }
+        Ok(())
+    }
+
+    /// Characterizes the fan by stepping it through duties and recording its speed at each.
+    ///
+    /// The fan is stepped up from a standstill to find its minimum start duty, then back down to
+    /// find its minimum sustain duty. The result is kept for linearising open-loop fan speeds, and
+    /// the minimum start duty becomes the zero RPM minimum running speed, so a fan that slows to it
+    /// can always restart.
+    ///
+    /// This takes a minute or more, during which temperatures aren't controlled, so it's only run
+    /// on request. Steps whose speed couldn't be read are skipped, and the fan is left at full
+    /// speed until the next update.
+    pub async fn characterize(&mut self, config: calibration::Config) -> Result<&Calibration> {
+        let calibration = self.sweep(&config).await;
+        // Make the next update reapply its fan speed, driving the fan hard until then.
+        self.fan_speed = None;
+        self.fan.set_fan_speed(&Speed::full())?;
+
+        let calibration = calibration?;
+        info!(
+            "fan calibrated: start at {}%, sustain at {}%, max {}rpm",
+            calibration.min_start_duty().get::<percent>(),
+            calibration.min_sustain_duty().get::<percent>(),
+            calibration.max_rpm().get::<revolution_per_minute>()
+        );
+        self.zero_rpm
+            .set_min_running_speed(calibration.min_start_duty());
+        Ok(self.calibration.insert(calibration))
+    }
+
+    /// Sweeps the fan up and back down through the calibration duties.
+    async fn sweep(&mut self, config: &calibration::Config) -> Result<Calibration> {
+        let duties = config.duties::<MAX_CALIBRATION_POINTS>()?;
+
+        let mut rising = Vec::<_, MAX_CALIBRATION_POINTS>::new();
+        for &duty in &duties {
+            if let Some(rpm) = self.settled_rpm(duty, config).await? {
+                rising.push((duty, rpm)).ok();
+            }
+        }
+        let mut falling = Vec::<_, MAX_CALIBRATION_POINTS>::new();
+        for &duty in duties.iter().rev() {
+            if let Some(rpm) = self.settled_rpm(duty, config).await? {
+                falling.push((duty, rpm)).ok();
+            }
+        }
+
+        Ok(Calibration::from_sweeps(rising, falling)?)
+    }
+
+    /// Drives the fan at `duty` and returns its speed once it stops changing, or `None` if it
+    /// couldn't be read.
+    async fn settled_rpm(
+        &mut self,
+        duty: Speed,
+        config: &calibration::Config,
+    ) -> Result<Option<AngularVelocity>> {
+        const SPIN_UP_TIME: Duration = Duration::from_secs(2);
+        const MAX_READINGS: usize = 10;
+
+        self.fan.set_fan_speed(&duty)?;
+        Timer::after(SPIN_UP_TIME).await;
+
+        let mut rpm = None;
+        for _ in 0..MAX_READINGS {
+            let next = match self.fan.rpm().await {
+                Ok(next) => next,
+                Err(e) => {
+                    warn!(
+                        "skipping calibration step at {}% duty: {}",
+                        duty.get::<percent>(),
+                        e
+                    );
+                    return Ok(None);
+                }
+            };
+            if rpm.is_some_and(|rpm| config.is_settled(rpm, next)) {
+                return Ok(Some(next));
+            }
+            rpm = Some(next);
+        }
+        warn!("fan speed didn't settle at {}% duty", duty.get::<percent>());
+        Ok(rpm)
+    }
+
+    /// Drives the fan at `fan_speed`, unless it's already running at it.
+    fn set_fan_speed(&mut self, fan_speed: Speed) -> Result<()> {
+        if self.fan_speed != Some(fan_speed) {
+            info!("new fan speed: {}%", fan_speed.get::<percent>());
+            self.fan.set_fan_speed(&fan_speed)?;
+            self.fan_speed = Some(fan_speed);
+        }
         Ok(())
     }
+
+    /// Returns the fan health, or `None` without a fault detector.
+    #[must_use]
+    pub fn fan_status(&self) -> Option<Status> {
+        self.fault_detector.as_ref().map(FaultDetector::status)
+    }
+
+    /// Returns the measured fan speed, or `None` if the tachometer couldn't be read.
+    async fn rpm(&mut self) -> Option<AngularVelocity> {
+        match self.fan.rpm().await {
+            Ok(rpm) => {
+                info!(
+                    "current fan speed: {}rpm",
+                    rpm.get::<revolution_per_minute>()
+                );
+                Some(rpm)
+            }
+            Err(e) => {
+                warn!("failed to read fan tachometer: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the aggregate temperature, or `None` if the sensors that could be read don't give
+    /// one.
+    ///
+    /// Records whether any sensor flagged its critical temperature limit.
+    async fn temp(&mut self, now: Time) -> Option<ThermodynamicTemperature> {
+        let mut temps = Vec::<Option<ThermodynamicTemperature>, MAX_SOURCES>::new();
+        self.critical = false;
+        for sensor in &mut self.sensors {
+            let temp = match sensor.reading(now).await {
+                Ok(reading) => {
+                    if reading.critical {
+                        warn!("temperature sensor critical limit reached");
+                        self.critical = true;
+                    }
+                    Some(reading.temp)
+                }
+                Err(e) => {
+                    warn!("failed to read temperature sensor: {}", e);
+                    None
+                }
+            };
+            temps.push(temp).ok();
+        }
+
+        match self.aggregate.reduce(temps) {
+            Ok(temp) => Some(temp),
+            Err(aggregate::Error::NoReadings) => None,
+            Err(e) => {
+                warn!("failed to aggregate temperatures: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Returns the ambient temperature, or `None` if it couldn't be read.
+    async fn ambient_temp(&mut self, now: Time) -> Option<ThermodynamicTemperature> {
+        let sensor = self.ambient_sensor.as_mut()?;
+        match sensor.temp(now).await {
+            Ok(temp) => Some(temp),
+            Err(e) => {
+                warn!("failed to read ambient temperature sensor: {}", e);
+                None
+            }
+        }
+    }
 }
@@ -1,6 +1,6 @@
 #![no_std]
 #![no_main]
-#![feature(async_fn_in_trait, error_in_core, type_alias_impl_trait)]
+#![feature(error_in_core, type_alias_impl_trait)]
 #![warn(clippy::suspicious, clippy::complexity, clippy::perf, clippy::pedantic)]
 #![allow(clippy::used_underscore_binding, clippy::missing_errors_doc)]
 
@@ -9,15 +9,31 @@ extern crate alloc;
 pub mod fan_control;
 
 use board::Board;
-use defmt::{error, info};
+use defmt::{error, info, warn};
 use defmt_rtt as _;
-use driver::{Fan, Mcp9808};
+use driver::{
+    fan::Speed,
+    mcp9808::{self, Limit},
+};
 use embassy_executor::Spawner;
-use embassy_rp::{gpio, peripherals, pio, pio::Pio};
+#[cfg(feature = "wifi")]
+use embassy_rp::{gpio, peripherals};
 use embassy_time::{Duration, Timer};
 use embedded_alloc::Heap;
+use fan_controller::{
+    closed_loop::{self, RpmRegulator},
+    filter::{Ema, Filter, Median},
+    health::{self, FaultDetector},
+    hysteresis::{self, Hysteresis},
+    ramp::{self, Ramp},
+    units::{AngularVelocity, Ratio, TemperatureInterval, ThermodynamicTemperature, Time},
+    zero_rpm::{self, ZeroRpm},
+};
 use panic_probe as _;
-use static_cell::make_static;
+use uom::si::{
+    angular_velocity::revolution_per_minute, ratio::percent, temperature_interval,
+    thermodynamic_temperature::degree_celsius, time::second,
+};
 
 use crate::fan_control::FanControl;
 
@@ -37,22 +53,102 @@ async fn wifi_task(
 }
 
 #[embassy_executor::main]
-async fn main(spawner: Spawner) {
+async fn main(#[cfg_attr(not(feature = "wifi"), allow(unused_variables))] spawner: Spawner) {
     init_allocator();
-    let mut board = Board::new().expect("failed to initialize board");
+    let mut board = Board::new().await.expect("failed to initialize board");
+    let mut sensors = mcp9808::discover(board.sensor_bus).await;
+    while sensors.is_empty() {
+        // The fans can't be controlled without a temperature, so run them flat out until a
+        // sensor turns up.
+        warn!("no temperature sensors found, running fans at full speed");
+        for result in [
+            board.fan_1.set_fan_speed(&Speed::full()),
+            board.fan_2.set_fan_speed(&Speed::full()),
+            board.fan_3.set_fan_speed(&Speed::full()),
+            board.fan_4.set_fan_speed(&Speed::full()),
+        ] {
+            if let Err(e) = result {
+                error!("failed to set fan speed: {}", e);
+            }
+        }
+        Timer::after(Duration::from_secs(5)).await;
+        sensors = mcp9808::discover(board.sensor_bus).await;
+    }
+    info!("discovered {} temperature sensors", sensors.len());
+    for sensor in &mut sensors {
+        // Until T_CRIT is set, the sensor's critical flag is meaningless and is ignored.
+        if let Err(e) = sensor.set_limit(Limit::Critical, critical_temp()).await {
+            warn!("failed to set temperature sensor critical limit: {}", e);
+        }
+    }
 
     #[cfg(feature = "wifi")]
     {
         use defmt::unwrap;
-        unwrap!(spawner.spawn(wifi_task(runner)));
+        unwrap!(spawner.spawn(wifi_task(board.wifi_runner)));
     }
 
     let mut fan_1_control = FanControl::builder()
         .fan(board.fan_1)
-        .sensor(board.sensor)
+        .sensors(sensors)
+        .filter(Median::<3>::new().chain(Ema::new(Time::new::<second>(5.0)).unwrap()))
+        .hysteresis(Hysteresis::new(hysteresis::Config {
+            rising: TemperatureInterval::new::<temperature_interval::degree_celsius>(0.5),
+            falling: TemperatureInterval::new::<temperature_interval::degree_celsius>(2.0),
+            min_speed_delta: Ratio::new::<percent>(2.0),
+        }))
+        .ramp(
+            Ramp::new(ramp::Config {
+                max_rise: 20.0,
+                max_fall: 5.0,
+            })
+            .unwrap(),
+        )
+        .regulator(Some(
+            RpmRegulator::new(closed_loop::Config {
+                max_rpm: AngularVelocity::new::<revolution_per_minute>(2000.0),
+                kp: 0.2,
+                ki: 0.3,
+            })
+            .unwrap(),
+        ))
+        .zero_rpm(
+            ZeroRpm::new(zero_rpm::Config {
+                stop_below: Some(ThermodynamicTemperature::new::<degree_celsius>(25.0)),
+                start_above: Some(ThermodynamicTemperature::new::<degree_celsius>(28.0)),
+                min_running_speed: Speed::new(Ratio::new::<percent>(20.0)).unwrap(),
+                kick_speed: Speed::new(Ratio::new::<percent>(100.0)).unwrap(),
+                kick_duration: Time::new::<second>(1.0),
+            })
+            .unwrap(),
+        )
+        .fault_detector(Some(
+            FaultDetector::new(health::Config {
+                max_rpm: AngularVelocity::new::<revolution_per_minute>(2000.0),
+                degraded_below: Ratio::new::<percent>(50.0),
+                min_duty: Speed::new(Ratio::new::<percent>(0.0)).unwrap(),
+                grace_period: Time::new::<second>(5.0),
+            })
+            .unwrap(),
+        ))
         .build()
         .unwrap();
 
+    #[cfg(feature = "calibrate")]
+    {
+        use fan_controller::calibration;
+
+        if let Err(e) = fan_1_control
+            .characterize(calibration::Config {
+                step: Ratio::new::<percent>(10.0),
+                ..calibration::Config::default()
+            })
+            .await
+        {
+            error!("failed to calibrate fan: {}", e);
+        }
+    }
+
     loop {
         // board.led.toggle();
         info!("hi");
@@ -64,6 +160,11 @@ async fn main(spawner: Spawner) {
     }
 }
 
+/// Returns the temperature at which the fans run flat out, whatever the control stages want.
+fn critical_temp() -> ThermodynamicTemperature {
+    ThermodynamicTemperature::new::<degree_celsius>(80.0)
+}
+
 fn init_allocator() {
     {
         use core::mem::MaybeUninit;
@@ -1,15 +1,22 @@
 #![no_std]
-#![feature(async_fn_in_trait, error_in_core, type_alias_impl_trait)]
+#![feature(error_in_core, type_alias_impl_trait)]
 #![warn(clippy::suspicious, clippy::complexity, clippy::perf, clippy::pedantic)]
 #![allow(clippy::missing_errors_doc, clippy::similar_names)]
 
 use defmt::info;
-use driver::{fan::Fan, Mcp9808};
+use driver::{
+    fan::{self, Fan},
+    mcp9808::{self, Bus},
+};
 use embassy_rp::{
     bind_interrupts, config,
-    gpio::{self, Level, Output},
-    i2c,
-    peripherals::{self, DMA_CH0, PIN_23, PIN_25, PIO0},
+    gpio::{self, Level},
+    i2c, peripherals,
+};
+#[cfg(feature = "wifi")]
+use embassy_rp::{
+    gpio::Output,
+    peripherals::{DMA_CH0, PIN_23, PIN_25, PIO0},
 };
 
 type Result<T> = core::result::Result<T, Error>;
@@ -35,32 +42,43 @@ use cyw43_pio::PioSpi;
 pub struct Board<'a> {
     pub wifi_runner: cyw43::Runner<'a, Output<'a, PIN_23>, PioSpi<'a, PIN_25, PIO0, 0, DMA_CH0>>,
     pub wifi_control: cyw43::Control<'a>,
-    pub sensor: Mcp9808<'a, Sensor>,
-    pub fan_1: Fan<'a, Fan1Control>,
-    pub fan_2: Fan<'a, Fan2Control>,
-    pub fan_3: Fan<'a, Fan3Control>,
-    pub fan_4: Fan<'a, Fan4Control>,
+    /// I2C0, where sensors are discovered.
+    pub sensor_bus: &'static Bus<'static, Sensor>,
+    pub fan_1: Fan<'static, Fan1Control>,
+    pub fan_2: Fan<'static, Fan2Control>,
+    pub fan_3: Fan<'static, Fan3Control>,
+    pub fan_4: Fan<'static, Fan4Control>,
 }
 
 #[cfg(not(feature = "wifi"))]
-pub struct Board<'a> {
-    pub sensor: Mcp9808<'a, Sensor>,
-    pub fan_1: Fan<'a, Fan1Control>,
-    pub fan_2: Fan<'a, Fan2Control>,
-    pub fan_3: Fan<'a, Fan3Control>,
-    pub fan_4: Fan<'a, Fan4Control>,
+pub struct Board {
+    pub led: Led,
+    /// I2C0, where sensors are discovered.
+    pub sensor_bus: &'static Bus<'static, Sensor>,
+    pub fan_1: Fan<'static, Fan1Control>,
+    pub fan_2: Fan<'static, Fan2Control>,
+    pub fan_3: Fan<'static, Fan3Control>,
+    pub fan_4: Fan<'static, Fan4Control>,
 }
 
 #[cfg(not(feature = "wifi"))]
 pub struct Led {
-    inner: gpio::Output<'a, peripherals::PIN_25>,
+    inner: gpio::Output<'static, peripherals::PIN_25>,
+}
+
+#[cfg(not(feature = "wifi"))]
+impl Led {
+    /// Toggles the onboard LED.
+    pub fn toggle(&mut self) {
+        self.inner.toggle();
+    }
 }
 
 #[cfg(feature = "wifi")]
 pub struct Led {}
 
+#[cfg(feature = "wifi")]
 impl<'a> Board<'a> {
-    #[cfg(feature = "wifi")]
     pub async fn new() -> Result<Self> {
         use embassy_rp::pio::{self, Pio};
         use static_cell::make_static;
@@ -98,43 +116,55 @@ impl<'a> Board<'a> {
             .set_power_management(cyw43::PowerManagementMode::PowerSave)
             .await;
 
-        let sensor = Mcp9808::new(p.I2C0, p.PIN_17, p.PIN_16, SensorInterrupts);
-        let fan_1 = Fan::new(p.PWM_CH0, p.PIN_0, p.PIN_1);
-        let fan_2 = Fan::new(p.PWM_CH2, p.PIN_4, p.PIN_5);
-        let fan_3 = Fan::new(p.PWM_CH4, p.PIN_8, p.PIN_9);
-        let fan_4 = Fan::new(p.PWM_CH6, p.PIN_12, p.PIN_13);
+        let sensor_bus: &Bus<Sensor> = make_static!(mcp9808::new_bus(
+            p.I2C0,
+            p.PIN_17,
+            p.PIN_16,
+            SensorInterrupts
+        ));
+        let fan_1 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH0, p.PIN_0, p.PIN_1)));
+        let fan_2 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH2, p.PIN_4, p.PIN_5)));
+        let fan_3 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH4, p.PIN_8, p.PIN_9)));
+        let fan_4 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH6, p.PIN_12, p.PIN_13)));
         info!("board initialized!");
 
         Ok(Self {
             wifi_runner: runner,
             wifi_control: control,
-            sensor,
+            sensor_bus,
             fan_1,
             fan_2,
             fan_3,
             fan_4,
         })
     }
+}
+
+#[cfg(not(feature = "wifi"))]
+impl Board {
+    // Async to match the wifi board, which has to wait for the wifi chip.
+    #[allow(clippy::unused_async)]
+    pub async fn new() -> Result<Self> {
+        use static_cell::make_static;
 
-    #[cfg(not(feature = "wifi"))]
-    pub fn new() -> Result<Self> {
         bind_interrupts!(struct Interrupts {
             I2C0_IRQ => i2c::InterruptHandler<peripherals::I2C0>;
         });
 
         let p = embassy_rp::init(config::Config::default());
         let led = gpio::Output::new(p.PIN_25, Level::Low);
-        let fan_1 = Fan::new(p.PWM_CH0, p.PIN_0, p.PIN_1);
-        let fan_2 = Fan::new(p.PWM_CH2, p.PIN_4, p.PIN_5);
-        let fan_3 = Fan::new(p.PWM_CH4, p.PIN_8, p.PIN_9);
-        let fan_4 = Fan::new(p.PWM_CH6, p.PIN_12, p.PIN_13);
+        let fan_1 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH0, p.PIN_0, p.PIN_1)));
+        let fan_2 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH2, p.PIN_4, p.PIN_5)));
+        let fan_3 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH4, p.PIN_8, p.PIN_9)));
+        let fan_4 = fan::on_slice(make_static!(fan::new_slice(p.PWM_CH6, p.PIN_12, p.PIN_13)));
 
-        let sensor = Mcp9808::new(p.I2C0, p.PIN_17, p.PIN_16, Interrupts);
+        let sensor_bus: &Bus<Sensor> =
+            make_static!(mcp9808::new_bus(p.I2C0, p.PIN_17, p.PIN_16, Interrupts));
         info!("board initialized!");
 
         Ok(Self {
-            sensor,
-            led,
+            led: Led { inner: led },
+            sensor_bus,
             fan_1,
             fan_2,
             fan_3,
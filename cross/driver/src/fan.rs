@@ -1,80 +1,100 @@
+use core::cell::RefCell;
+
 use defmt::info;
-use embassy_rp::{
-    clocks,
-    pwm::{self},
-    Peripheral,
-};
-use embassy_time::Delay;
-use embedded_hal_async::delay::DelayUs;
-pub use fan_controller::decode::fan::Speed;
+use embassy_rp::{clocks, pwm, Peripheral};
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_time::{Delay, Instant};
+use embedded_hal_async::delay::DelayNs;
 use fan_controller::{
-    decode,
+    decode, fan,
     units::{Frequency, Time},
 };
-use uom::si::{frequency::hertz, ratio::percent, time::millisecond};
+pub use fan_controller::{
+    decode::fan::{DriveMode, PeriodConfig, Speed, Tachometer},
+    fan::Error,
+};
+use fixed::FixedU16;
+use uom::si::{frequency::hertz, ratio::percent, time::microsecond};
+
+/// Represents a PWM slice shared by a fan's output and tachometer counter.
+pub type Slice<'a, C> = Mutex<CriticalSectionRawMutex, RefCell<pwm::Pwm<'a, C>>>;
 
-type Result<T> = core::result::Result<T, Error>;
+/// Represents a fan on an RP2040 PWM slice.
+pub type Fan<'a, C> = fan::Fan<SliceOutput<'a, C>, SliceCounter<'a, C>, SystemClock>;
 
-/// Represents a fan driver error.
-#[derive(Debug, thiserror::Error, defmt::Format)]
-pub enum Error {
-    #[error("not enough samples")]
-    NotEnoughSamples(),
-    /// A decode error occurred.
-    #[error("decode error")]
-    DecodeError(#[from] decode::fan::Error),
+/// Returns a PWM slice that drives a fan from channel A and counts its tachometer pulses on
+/// channel B.
+#[must_use]
+pub fn new_slice<'a, C: pwm::Channel>(
+    channel: impl Peripheral<P = C> + 'a,
+    control: impl Peripheral<P = impl pwm::PwmPinA<C>> + 'a,
+    tachometer: impl Peripheral<P = impl pwm::PwmPinB<C>> + 'a,
+) -> Slice<'a, C> {
+    Mutex::new(RefCell::new(pwm::Pwm::new_output_input(
+        channel,
+        control,
+        tachometer,
+        pwm::InputMode::FallingEdge,
+        pwm::Config::default(),
+    )))
 }
 
-pub struct Fan<'a, C: pwm::Channel> {
-    pin: pwm::Pwm<'a, C>,
+/// Returns the fan on `slice`.
+#[must_use]
+pub fn on_slice<'a, C: pwm::Channel>(slice: &'a Slice<'a, C>) -> Fan<'a, C> {
+    Fan::new(SliceOutput { slice }, SliceCounter { slice }, SystemClock)
 }
 
-impl<'a, C: pwm::Channel> Fan<'a, C> {
-    #[must_use]
-    pub fn new(
-        channel: impl Peripheral<P = C> + 'a,
-        control: impl Peripheral<P = impl pwm::PwmPinA<C>> + 'a,
-        tachometer: impl Peripheral<P = impl pwm::PwmPinB<C>> + 'a,
-    ) -> Self {
-        Self {
-            pin: pwm::Pwm::new_output_input(
-                channel,
-                control,
-                tachometer,
-                pwm::InputMode::FallingEdge,
-                pwm::Config::default(),
-            ),
-        }
-    }
+/// Represents the PWM output of a fan slice.
+pub struct SliceOutput<'a, C: pwm::Channel> {
+    slice: &'a Slice<'a, C>,
+}
 
-    pub fn set_fan_speed(&mut self, speed: &Speed) {
-        info!("setting fan to {}% speed", speed.get::<percent>());
-        let params = speed.pwm_config(Frequency::new::<hertz>(f64::from(clocks::clk_sys_freq())));
+impl<'a, C: pwm::Channel> fan::Output for SliceOutput<'a, C> {
+    fn set_duty(&mut self, duty: Speed, mode: DriveMode) -> decode::fan::Result<()> {
+        info!("setting fan to {}% speed", duty.get::<percent>());
+        let params = duty.pwm_config(
+            Frequency::new::<hertz>(f64::from(clocks::clk_sys_freq())),
+            mode,
+        )?;
 
         let mut config = pwm::Config::default();
+        config.divider = FixedU16::from_bits(params.divider.to_bits());
         config.top = params.top;
         config.compare_a = params.compare;
         config.compare_b = params.compare;
-        self.pin.set_config(&config);
+        self.slice.lock(|pwm| pwm.borrow_mut().set_config(&config));
+        Ok(())
     }
+}
 
-    /// Returns the current fan rotation frequency.
-    pub async fn fan_freq(&mut self) -> Result<Frequency> {
-        let sample_duration = Time::new::<millisecond>(500.0);
+/// Represents the tachometer pulse counter of a fan slice.
+pub struct SliceCounter<'a, C: pwm::Channel> {
+    slice: &'a Slice<'a, C>,
+}
 
-        // We need to sample the number of pulses over a fixed duration to determine fan frequency.
-        self.pin.set_counter(0);
-        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-        Delay {}
-            .delay_ms(sample_duration.get::<millisecond>() as u32)
-            .await;
-        let pulse_count = self.pin.counter();
+impl<'a, C: pwm::Channel> fan::PulseCounter for SliceCounter<'a, C> {
+    fn count(&mut self) -> u16 {
+        self.slice.lock(|pwm| pwm.borrow().counter())
+    }
+
+    fn reset(&mut self) {
+        self.slice.lock(|pwm| pwm.borrow_mut().set_counter(0));
+    }
+}
 
-        if pulse_count < 2 {
-            return Err(Error::NotEnoughSamples());
-        }
+/// Represents the embassy time driver as a fan clock.
+pub struct SystemClock;
+
+impl fan::Clock for SystemClock {
+    fn now(&self) -> Time {
+        #[allow(clippy::cast_precision_loss)]
+        Time::new::<microsecond>(Instant::now().as_micros() as f64)
+    }
+}
 
-        let frequency = 1.0 / (sample_duration / f64::from(pulse_count));
-        Ok(frequency)
+impl DelayNs for SystemClock {
+    async fn delay_ns(&mut self, ns: u32) {
+        Delay.delay_ns(ns).await;
     }
 }
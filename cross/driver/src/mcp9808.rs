@@ -1,102 +1,54 @@
+use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
 use embassy_rp::{i2c, interrupt::typelevel::Binding, Peripheral};
-pub use fan_controller::decode::mcp9808::{
-    Address, DeviceId, ManufacturerId, ManufacturerIdPayload, Revision, TemperaturePayload,
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::Delay;
+use fan_controller::sensor;
+pub use fan_controller::{
+    decode::mcp9808::{
+        Address, AlertMode, AlertPolarity, AlertSelect, Config, DeviceId, Hysteresis,
+        ManufacturerId, Resolution, Revision, TemperatureReading,
+    },
+    sensor::mcp9808::{Error, Limit, DEFAULT_ADDRESS, DEVICE_ID, MANUFACTURER_ID, MAX_SENSORS},
 };
-use fan_controller::{
-    decode::{self, mcp9808::DeviceIdPayload},
-    units::ThermodynamicTemperature,
-};
-
-type Result<T> = core::result::Result<T, Error>;
+use heapless::Vec;
 
-/// Represents an error.
-#[derive(Debug, thiserror::Error, defmt::Format)]
-pub enum Error {
-    #[error("decode error")]
-    DecodeError(#[from] decode::mcp9808::Error),
-    #[error("i2c error")]
-    I2CError(i2c::Error),
-}
+/// Represents an I2C bus shared by several devices.
+pub type Bus<'a, T> = Mutex<CriticalSectionRawMutex, i2c::I2c<'a, T, i2c::Async>>;
 
-/// Represents a MCP9808 register.
+/// Represents a MCP9808 on an RP2040 I2C bus.
 ///
-/// See: datasheet § 5.1, page 16.
-#[repr(u8)]
-enum Register {
-    Temperature = 0x05,
-    ManufacturerId = 0x06,
-    DeviceId = 0x07,
-}
-
-#[derive(derive_builder::Builder)]
-#[builder(no_std, pattern = "owned")]
-pub struct Mcp9808<'a, T: i2c::Instance> {
-    #[builder(setter(custom))]
-    i2c: i2c::I2c<'a, T, i2c::Async>,
+/// Devices can only share a bus that lives for the rest of the program.
+pub type Mcp9808<'a, T> = sensor::mcp9808::Mcp9808<
+    I2cDevice<'a, CriticalSectionRawMutex, i2c::I2c<'static, T, i2c::Async>>,
+    Delay,
+>;
+
+/// Returns an I2C bus that several MCP9808s can share.
+#[must_use]
+pub fn new_bus<'a, T: i2c::Instance>(
+    peripheral: impl Peripheral<P = T> + 'a,
+    scl_pin: impl Peripheral<P = impl i2c::SclPin<T>> + 'a,
+    sda_pin: impl Peripheral<P = impl i2c::SdaPin<T>> + 'a,
+    irq: impl Binding<T::Interrupt, i2c::InterruptHandler<T>>,
+) -> Bus<'a, T> {
+    Mutex::new(i2c::I2c::new_async(
+        peripheral,
+        scl_pin,
+        sda_pin,
+        irq,
+        i2c::Config::default(),
+    ))
 }
 
-/// Standard MCP9808 manufacturer ID.
-pub const MANUFACTURER_ID: ManufacturerId = ManufacturerId(0x54);
-/// Standard MCP9808 device ID.
-pub const DEVICE_ID: DeviceId = DeviceId(0x04);
-/// Default MCP9808 I2C address.
-pub const DEFAULT_ADDRESS: Address = Address(0x18);
-
-impl<'a, T: i2c::Instance> Mcp9808<'a, T> {
-    #[must_use]
-    pub fn new(
-        peripheral: impl Peripheral<P = T> + 'a,
-        scl_pin: impl Peripheral<P = impl i2c::SclPin<T>> + 'a,
-        sda_pin: impl Peripheral<P = impl i2c::SdaPin<T>> + 'a,
-        irq: impl Binding<T::Interrupt, i2c::InterruptHandler<T>>,
-    ) -> Self {
-        Self {
-            i2c: i2c::I2c::new_async(peripheral, scl_pin, sda_pin, irq, i2c::Config::default()),
-        }
-    }
-
-    pub async fn temp(&mut self) -> Result<ThermodynamicTemperature> {
-        let mut payload = TemperaturePayload::ZERO;
-
-        self.i2c
-            .write_async(*DEFAULT_ADDRESS, [Register::Temperature as u8])
-            .await?;
-        self.i2c
-            .read_async(*DEFAULT_ADDRESS, payload.as_raw_mut_slice())
-            .await?;
-
-        Ok(decode::mcp9808::decode_temperature(payload)?)
-    }
-
-    pub async fn manufacturer_id(&mut self) -> Result<ManufacturerId> {
-        let mut payload = ManufacturerIdPayload::ZERO;
-
-        self.i2c
-            .write_async(*DEFAULT_ADDRESS, [Register::ManufacturerId as u8])
-            .await?;
-        self.i2c
-            .read_async(*DEFAULT_ADDRESS, payload.as_raw_mut_slice())
-            .await?;
-
-        Ok(decode::mcp9808::decode_manufacturer_id(payload)?)
-    }
-
-    pub async fn device_id(&mut self) -> Result<(DeviceId, Revision)> {
-        let mut payload = DeviceIdPayload::ZERO;
-
-        self.i2c
-            .write_async(*DEFAULT_ADDRESS, [Register::DeviceId as u8])
-            .await?;
-        self.i2c
-            .read_async(*DEFAULT_ADDRESS, payload.as_raw_mut_slice())
-            .await?;
-
-        Ok(decode::mcp9808::decode_device_id(payload)?)
-    }
+/// Returns the MCP9808 at `address` on `bus`.
+#[must_use]
+pub fn on_bus<'a, T: i2c::Instance>(bus: &'a Bus<'static, T>, address: Address) -> Mcp9808<'a, T> {
+    Mcp9808::new(I2cDevice::new(bus), Delay, address)
 }
 
-impl From<i2c::Error> for Error {
-    fn from(value: i2c::Error) -> Self {
-        Error::I2CError(value)
-    }
+/// Returns the MCP9808s found on `bus`, whatever addresses they're strapped to.
+pub async fn discover<'a, T: i2c::Instance>(
+    bus: &'a Bus<'static, T>,
+) -> Vec<Mcp9808<'a, T>, MAX_SENSORS> {
+    sensor::mcp9808::discover(|address| on_bus(bus, address)).await
 }
@@ -18,18 +18,20 @@ mod tests {
 
     #[init]
     fn init() -> Board<'static> {
-        board::Board::new().expect("failed to initialize board")
+        block_on(board::Board::new()).expect("failed to initialize board")
     }
 
     #[test]
     fn test_manufacturer_id(board: &mut Board<'static>) {
-        let manufacturer_id = block_on(board.sensor.manufacturer_id()).unwrap();
+        let mut sensor = mcp9808::on_bus(board.sensor_bus, mcp9808::DEFAULT_ADDRESS);
+        let manufacturer_id = block_on(sensor.manufacturer_id()).unwrap();
         assert_eq!(manufacturer_id, mcp9808::MANUFACTURER_ID);
     }
 
     #[test]
     fn test_device_id_and_revision(board: &mut Board<'static>) {
-        let (device_id, revision) = block_on(board.sensor.device_id()).unwrap();
+        let mut sensor = mcp9808::on_bus(board.sensor_bus, mcp9808::DEFAULT_ADDRESS);
+        let (device_id, revision) = block_on(sensor.device_id()).unwrap();
         assert_eq!(device_id, mcp9808::DEVICE_ID);
         assert_eq!(revision, Revision(0x00));
     }
@@ -0,0 +1,264 @@
+use heapless::Vec;
+use uom::si::thermodynamic_temperature::degree_celsius;
+
+use crate::units::ThermodynamicTemperature;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a temperature aggregation error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// None of the sources produced a reading.
+    #[error("no temperature readings")]
+    NoReadings,
+    /// More sources were provided than the aggregate can hold.
+    #[error("too many temperature sources: expected x≤{0} sources")]
+    TooManySources(usize),
+    /// A source weight is negative or not finite.
+    #[error("invalid weight: expected 0≤x<∞, got {0}")]
+    InvalidWeight(f64),
+    /// The weights of every source that produced a reading add up to zero.
+    #[error("total weight of temperature readings is zero")]
+    ZeroTotalWeight,
+}
+
+/// Default maximum number of temperature sources.
+pub const MAX_SOURCES: usize = 8;
+
+/// Represents how several temperature readings are combined into one.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Reducer {
+    /// The hottest reading.
+    #[default]
+    Max,
+    /// The mean of all readings.
+    Mean,
+    /// The mean of all readings, weighted by their source's weight.
+    WeightedMean,
+    /// The middle reading, or the mean of the two middle readings.
+    Median,
+}
+
+/// Represents an aggregate of up to `N` temperature sources.
+///
+/// Sources are identified by their position. Sources without a reading, e.g. because the sensor
+/// read failed, are left out of the aggregate.
+#[derive(Debug, Clone)]
+pub struct Aggregate<const N: usize = MAX_SOURCES> {
+    reducer: Reducer,
+    weights: Vec<f64, N>,
+}
+
+impl<const N: usize> Aggregate<N> {
+    /// Creates an aggregate where every source has a weight of 1.
+    #[must_use]
+    pub fn new(reducer: Reducer) -> Self {
+        Self {
+            reducer,
+            weights: Vec::new(),
+        }
+    }
+
+    /// Sets the weight of each source, in source order.
+    ///
+    /// Sources without a weight have a weight of 1.
+    pub fn with_weights<I>(mut self, weights: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        self.weights.clear();
+        for weight in weights {
+            if !weight.is_finite() || weight < 0.0 {
+                return Err(Error::InvalidWeight(weight));
+            }
+            self.weights
+                .push(weight)
+                .map_err(|_| Error::TooManySources(N))?;
+        }
+        Ok(self)
+    }
+
+    /// Returns how readings are combined.
+    #[must_use]
+    pub fn reducer(&self) -> Reducer {
+        self.reducer
+    }
+
+    /// Returns the weight of the source at `index`.
+    #[must_use]
+    pub fn weight(&self, index: usize) -> f64 {
+        self.weights.get(index).copied().unwrap_or(1.0)
+    }
+
+    /// Combines one reading per source, in source order, into a single temperature.
+    pub fn reduce<I>(&self, temps: I) -> Result<ThermodynamicTemperature>
+    where
+        I: IntoIterator<Item = Option<ThermodynamicTemperature>>,
+    {
+        let mut readings = Vec::<(f64, f64), N>::new();
+        for (i, temp) in temps.into_iter().enumerate() {
+            if i >= N {
+                return Err(Error::TooManySources(N));
+            }
+            if let Some(temp) = temp {
+                readings
+                    .push((temp.get::<degree_celsius>(), self.weight(i)))
+                    .map_err(|_| Error::TooManySources(N))?;
+            }
+        }
+
+        if readings.is_empty() {
+            return Err(Error::NoReadings);
+        }
+
+        let temp = match self.reducer {
+            Reducer::Max => readings
+                .iter()
+                .map(|&(temp, _)| temp)
+                .fold(f64::NEG_INFINITY, f64::max),
+            Reducer::Mean => Self::weighted_mean(readings.iter().map(|&(temp, _)| (temp, 1.0)))?,
+            Reducer::WeightedMean => Self::weighted_mean(readings.iter().copied())?,
+            Reducer::Median => {
+                readings.sort_unstable_by(|(a, _), (b, _)| a.total_cmp(b));
+                let mid = readings.len() / 2;
+                if readings.len() % 2 == 0 {
+                    (readings[mid - 1].0 + readings[mid].0) / 2.0
+                } else {
+                    readings[mid].0
+                }
+            }
+        };
+
+        Ok(ThermodynamicTemperature::new::<degree_celsius>(temp))
+    }
+
+    fn weighted_mean(readings: impl Iterator<Item = (f64, f64)>) -> Result<f64> {
+        let (sum, total_weight) = readings
+            .fold((0.0, 0.0), |(sum, total_weight), (temp, weight)| {
+                (sum + temp * weight, total_weight + weight)
+            });
+
+        if total_weight <= 0.0 {
+            return Err(Error::ZeroTotalWeight);
+        }
+        Ok(sum / total_weight)
+    }
+}
+
+impl<const N: usize> Default for Aggregate<N> {
+    fn default() -> Self {
+        Self::new(Reducer::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    fn temps(temps: &[Option<f64>]) -> impl Iterator<Item = Option<ThermodynamicTemperature>> + '_ {
+        temps
+            .iter()
+            .map(|temp| temp.map(ThermodynamicTemperature::new::<degree_celsius>))
+    }
+
+    fn reduce<const N: usize>(aggregate: &Aggregate<N>, readings: &[Option<f64>]) -> f64 {
+        aggregate
+            .reduce(temps(readings))
+            .unwrap()
+            .get::<degree_celsius>()
+    }
+
+    const READINGS: [Option<f64>; 4] = [Some(30.0), Some(50.0), Some(35.0), Some(41.0)];
+
+    #[test]
+    fn max() {
+        let aggregate = Aggregate::<8>::new(Reducer::Max);
+        assert_float_eq!(reduce(&aggregate, &READINGS), 50.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn mean() {
+        let aggregate = Aggregate::<8>::new(Reducer::Mean)
+            .with_weights([10.0, 0.0])
+            .unwrap();
+        assert_float_eq!(reduce(&aggregate, &READINGS), 39.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn weighted_mean() {
+        let aggregate = Aggregate::<8>::new(Reducer::WeightedMean)
+            .with_weights([2.0, 0.0, 1.0])
+            .unwrap();
+        // (30 × 2 + 50 × 0 + 35 × 1 + 41 × 1) / 4
+        assert_float_eq!(reduce(&aggregate, &READINGS), 34.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn median() {
+        let aggregate = Aggregate::<8>::new(Reducer::Median);
+        assert_float_eq!(reduce(&aggregate, &READINGS), 38.0, abs <= 1e-9);
+        assert_float_eq!(reduce(&aggregate, &READINGS[..3]), 35.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn missing_readings_are_skipped() {
+        let readings = [Some(30.0), None, Some(35.0), Some(41.0)];
+
+        let aggregate = Aggregate::<8>::new(Reducer::Max);
+        assert_float_eq!(reduce(&aggregate, &readings), 41.0, abs <= 1e-9);
+
+        let aggregate = Aggregate::<8>::new(Reducer::WeightedMean)
+            .with_weights([1.0, 100.0, 1.0, 2.0])
+            .unwrap();
+        // (30 × 1 + 35 × 1 + 41 × 2) / 4
+        assert_float_eq!(reduce(&aggregate, &readings), 36.75, abs <= 1e-9);
+    }
+
+    #[test]
+    fn no_readings() {
+        let aggregate = Aggregate::<8>::default();
+        assert_eq!(
+            aggregate.reduce(temps(&[None, None])).unwrap_err(),
+            Error::NoReadings
+        );
+        assert_eq!(aggregate.reduce(temps(&[])).unwrap_err(), Error::NoReadings);
+    }
+
+    #[test]
+    fn zero_total_weight() {
+        let aggregate = Aggregate::<8>::new(Reducer::WeightedMean)
+            .with_weights([0.0, 1.0])
+            .unwrap();
+        assert_eq!(
+            aggregate.reduce(temps(&[Some(30.0), None])).unwrap_err(),
+            Error::ZeroTotalWeight
+        );
+    }
+
+    #[test]
+    fn too_many_sources() {
+        let aggregate = Aggregate::<2>::default();
+        assert_eq!(
+            aggregate.reduce(temps(&READINGS)).unwrap_err(),
+            Error::TooManySources(2)
+        );
+        assert_eq!(
+            Aggregate::<2>::default()
+                .with_weights([1.0, 1.0, 1.0])
+                .unwrap_err(),
+            Error::TooManySources(2)
+        );
+    }
+
+    #[test]
+    fn invalid_weight() {
+        assert_eq!(
+            Aggregate::<8>::default()
+                .with_weights([1.0, -1.0])
+                .unwrap_err(),
+            Error::InvalidWeight(-1.0)
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use heapless::Vec;
+use num_traits::Float;
+use uom::si::{angular_velocity::revolution_per_minute, ratio::percent};
+
+use crate::{
+    decode::fan,
+    units::{AngularVelocity, Ratio},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a fan calibration error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The duty step is outside 0..100%.
+    #[error("invalid duty step: expected 0<x≤100%, got {0}%")]
+    InvalidStep(f64),
+    /// The sweep has more points than the calibration can hold.
+    #[error("too many calibration points: expected x≤{0} points")]
+    TooManyPoints(usize),
+    /// The fan didn't spin at any duty.
+    #[error("fan never spun")]
+    NoRotation,
+    /// A fan decode error occurred.
+    #[error("fan decode error: {0}")]
+    FanDecodeError(#[from] fan::Error),
+}
+
+/// Default maximum number of calibration points, enough for 5% steps.
+pub const MAX_CALIBRATION_POINTS: usize = 21;
+
+/// Represents a measured calibration point.
+pub type Point = (fan::Speed, AngularVelocity);
+
+/// Represents fan characterization parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The duty step between calibration points.
+    pub step: Ratio,
+    /// Two consecutive readings within this fraction of each other count as settled.
+    pub settle_tolerance: Ratio,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            step: Ratio::new::<percent>(5.0),
+            settle_tolerance: Ratio::new::<percent>(2.0),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the duties to step through, from 0% to 100%.
+    ///
+    /// The fan is characterized by stepping up through these duties, which finds the minimum
+    /// start duty, then back down, which finds the minimum sustain duty.
+    pub fn duties<const N: usize>(&self) -> Result<Vec<fan::Speed, N>> {
+        let step = self.step.get::<percent>();
+        if step.is_nan() || step <= 0.0 || step > 100.0 {
+            return Err(Error::InvalidStep(step));
+        }
+
+        let mut duties = Vec::new();
+        for i in 0u32.. {
+            let duty = (f64::from(i) * step).min(100.0);
+            duties
+                .push(fan::Speed::new(Ratio::new::<percent>(duty))?)
+                .map_err(|_| Error::TooManyPoints(N))?;
+            if duty >= 100.0 {
+                break;
+            }
+        }
+        Ok(duties)
+    }
+
+    /// Returns `true` if `next` is close enough to `previous` for the fan to have settled.
+    #[must_use]
+    pub fn is_settled(&self, previous: AngularVelocity, next: AngularVelocity) -> bool {
+        let (previous, next) = (
+            previous.get::<revolution_per_minute>(),
+            next.get::<revolution_per_minute>(),
+        );
+        let tolerance = previous.max(next) * self.settle_tolerance.get::<percent>() / 100.0;
+        Float::abs(next - previous) <= tolerance
+    }
+}
+
+/// Represents a measured mapping from fan duty to fan speed.
+///
+/// Lets curves be expressed in percent of the fan's maximum RPM rather than in duty, which
+/// rarely maps linearly to speed or airflow.
+#[derive(Debug, Clone)]
+pub struct Calibration<const N: usize = MAX_CALIBRATION_POINTS> {
+    // Points where the fan was spinning, by increasing duty, with non-decreasing RPM.
+    duties: Vec<f64, N>,
+    rpms: Vec<f64, N>,
+    min_start_duty: fan::Speed,
+}
+
+impl<const N: usize> Calibration<N> {
+    /// Creates a calibration from sweeps stepping up and back down through duties.
+    ///
+    /// The lowest duty at which the fan spins on the way up is the minimum start duty. The
+    /// sweep on the way down, with the fan already spinning, gives the duty to RPM table and
+    /// the minimum sustain duty.
+    pub fn from_sweeps<I, J>(rising: I, falling: J) -> Result<Self>
+    where
+        I: IntoIterator<Item = Point>,
+        J: IntoIterator<Item = Point>,
+    {
+        let min_start_duty = rising
+            .into_iter()
+            .filter(|(_, rpm)| rpm.get::<revolution_per_minute>() > 0.0)
+            .map(|(duty, _)| duty)
+            .reduce(|a, b| if b < a { b } else { a })
+            .ok_or(Error::NoRotation)?;
+
+        let mut points = Vec::<(f64, f64), N>::new();
+        for (duty, rpm) in falling {
+            let rpm = rpm.get::<revolution_per_minute>();
+            if rpm > 0.0 {
+                points
+                    .push((duty.get::<percent>(), rpm))
+                    .map_err(|_| Error::TooManyPoints(N))?;
+            }
+        }
+        if points.is_empty() {
+            return Err(Error::NoRotation);
+        }
+        points.sort_unstable_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        // Tach noise can make RPM dip slightly as duty rises, which would make the table
+        // ambiguous to invert.
+        let mut duties = Vec::new();
+        let mut rpms = Vec::new();
+        let mut max_rpm = 0.0;
+        for (duty, rpm) in points {
+            max_rpm = f64::max(max_rpm, rpm);
+            duties.push(duty).ok();
+            rpms.push(max_rpm).ok();
+        }
+
+        Ok(Self {
+            duties,
+            rpms,
+            min_start_duty,
+        })
+    }
+
+    /// Returns the lowest duty that gets a stopped fan spinning.
+    #[must_use]
+    pub fn min_start_duty(&self) -> fan::Speed {
+        self.min_start_duty
+    }
+
+    /// Returns the lowest duty that keeps a spinning fan spinning.
+    #[must_use]
+    pub fn min_sustain_duty(&self) -> fan::Speed {
+        Self::speed(self.duties[0])
+    }
+
+    /// Returns the fan's speed at full duty.
+    #[must_use]
+    pub fn max_rpm(&self) -> AngularVelocity {
+        AngularVelocity::new::<revolution_per_minute>(self.rpms[self.rpms.len() - 1])
+    }
+
+    /// Returns the speed a spinning fan runs at for `duty`.
+    #[must_use]
+    pub fn rpm(&self, duty: fan::Speed) -> AngularVelocity {
+        let duty = duty.get::<percent>();
+        let rpm = if duty < self.duties[0] {
+            0.0
+        } else {
+            Self::interpolate(&self.duties, &self.rpms, duty)
+        };
+        AngularVelocity::new::<revolution_per_minute>(rpm)
+    }
+
+    /// Returns the duty that spins the fan at `speed`, read as a percentage of its max RPM.
+    ///
+    /// Speeds the fan can't run as slowly as give the minimum sustain duty. Zero stays zero, so
+    /// the fan can still be stopped.
+    #[must_use]
+    pub fn duty(&self, speed: fan::Speed) -> fan::Speed {
+        let fraction = speed.get::<percent>() / 100.0;
+        if fraction <= 0.0 {
+            return Self::speed(0.0);
+        }
+
+        let rpm = fraction * self.rpms[self.rpms.len() - 1];
+        Self::speed(Self::interpolate(&self.rpms, &self.duties, rpm))
+    }
+
+    /// Linearly interpolates `ys` at `x` over non-decreasing `xs`, holding the end values.
+    fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+        let i = xs.partition_point(|&point| point < x);
+        if i == 0 {
+            return ys[0];
+        }
+        if i == xs.len() {
+            return ys[ys.len() - 1];
+        }
+
+        let (x0, x1, y0, y1) = (xs[i - 1], xs[i], ys[i - 1], ys[i]);
+        if x1 <= x0 {
+            return y1;
+        }
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+
+    fn speed(duty: f64) -> fan::Speed {
+        // Duties come from validated speeds, so they're always within 0..=100%.
+        fan::Speed::new(Ratio::new::<percent>(duty.clamp(0.0, 100.0))).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+    use crate::test_util::{rpm, speed};
+
+    /// Simulates a fan that starts at 30% duty, stalls below 20%, and runs at `2000 × √duty`.
+    fn sweep(duties: &[fan::Speed], starting: bool) -> std::vec::Vec<Point> {
+        let threshold = if starting { 30.0 } else { 20.0 };
+        duties
+            .iter()
+            .map(|&duty| {
+                let d = duty.get::<percent>();
+                let rpm = if d >= threshold {
+                    2000.0 * (d / 100.0).sqrt()
+                } else {
+                    0.0
+                };
+                (duty, AngularVelocity::new::<revolution_per_minute>(rpm))
+            })
+            .collect()
+    }
+
+    fn new_calibration() -> Calibration {
+        let duties = Config {
+            step: Ratio::new::<percent>(10.0),
+            ..Config::default()
+        }
+        .duties::<11>()
+        .unwrap();
+        let mut falling = sweep(&duties, false);
+        falling.reverse();
+        Calibration::from_sweeps(sweep(&duties, true), falling).unwrap()
+    }
+
+    #[test]
+    fn duties() {
+        let duties = Config {
+            step: Ratio::new::<percent>(30.0),
+            ..Config::default()
+        }
+        .duties::<8>()
+        .unwrap();
+        assert_eq!(
+            &duties[..],
+            &[
+                speed(0.0),
+                speed(30.0),
+                speed(60.0),
+                speed(90.0),
+                speed(100.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_duties() {
+        let config = Config {
+            step: Ratio::new::<percent>(0.0),
+            ..Config::default()
+        };
+        assert_eq!(config.duties::<8>().unwrap_err(), Error::InvalidStep(0.0));
+        assert_eq!(
+            Config::default().duties::<8>().unwrap_err(),
+            Error::TooManyPoints(8)
+        );
+    }
+
+    #[test]
+    fn settled() {
+        let config = Config::default();
+        assert!(config.is_settled(rpm(1000.0), rpm(1015.0)));
+        assert!(!config.is_settled(rpm(1000.0), rpm(1100.0)));
+        assert!(config.is_settled(rpm(0.0), rpm(0.0)));
+    }
+
+    #[test]
+    fn start_and_sustain_duty() {
+        let calibration = new_calibration();
+        assert_eq!(calibration.min_start_duty(), speed(30.0));
+        assert_eq!(calibration.min_sustain_duty(), speed(20.0));
+        assert_float_eq!(
+            calibration.max_rpm().get::<revolution_per_minute>(),
+            2000.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn duty_to_rpm() {
+        let calibration = new_calibration();
+        let rpm = |duty| calibration.rpm(speed(duty)).get::<revolution_per_minute>();
+        assert_float_eq!(rpm(10.0), 0.0, abs <= 1e-9);
+        assert_float_eq!(rpm(20.0), 2000.0 * 0.2_f64.sqrt(), abs <= 1e-9);
+        // Halfway between the 20% and 30% points.
+        assert_float_eq!(
+            rpm(25.0),
+            1000.0 * (0.2_f64.sqrt() + 0.3_f64.sqrt()),
+            abs <= 1e-9
+        );
+        assert_float_eq!(rpm(100.0), 2000.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn linearised_duty() {
+        let calibration = new_calibration();
+        let duty = |speed_pct| calibration.duty(speed(speed_pct)).get::<percent>();
+
+        // 1000rpm is 25% duty on a √duty fan, give or take the interpolation between points.
+        assert_float_eq!(duty(50.0), 25.0, abs <= 0.5);
+        assert_float_eq!(duty(100.0), 100.0, abs <= 1e-9);
+        // Too slow for the fan to sustain.
+        assert_float_eq!(duty(10.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(duty(0.0), 0.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn round_trip() {
+        let calibration = new_calibration();
+        for pct in [50.0, 60.0, 85.0, 100.0] {
+            let rpm = calibration.rpm(calibration.duty(speed(pct)));
+            assert_float_eq!(
+                rpm.get::<revolution_per_minute>(),
+                2000.0 * pct / 100.0,
+                abs <= 1e-6
+            );
+        }
+    }
+
+    #[test]
+    fn no_rotation() {
+        let duties = Config::default().duties::<21>().unwrap();
+        let stopped: std::vec::Vec<Point> = duties.iter().map(|&duty| (duty, rpm(0.0))).collect();
+        assert_eq!(
+            Calibration::<21>::from_sweeps(stopped.clone(), stopped).unwrap_err(),
+            Error::NoRotation
+        );
+    }
+}
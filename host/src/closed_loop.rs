@@ -0,0 +1,278 @@
+use uom::si::{angular_velocity::revolution_per_minute, ratio::percent, time::second};
+
+use crate::{
+    decode::fan,
+    units::{AngularVelocity, Ratio, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents an RPM regulator error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The maximum RPM isn't positive.
+    #[error("invalid max rpm: expected x>0rpm, got {0}rpm")]
+    InvalidMaxRpm(f64),
+    /// A gain is negative or not finite.
+    #[error("invalid gain: expected 0≤x<∞, got {0}")]
+    InvalidGain(f64),
+    /// A fan decode error occurred.
+    #[error("fan decode error: {0}")]
+    FanDecodeError(#[from] fan::Error),
+}
+
+/// Represents RPM regulator parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The RPM that a target fan speed of 100% stands for. Target speeds are read as a percentage
+    /// of this.
+    pub max_rpm: AngularVelocity,
+    /// Proportional gain, in % duty per % of max RPM of error.
+    pub kp: f64,
+    /// Integral gain, in % duty per % of max RPM of error per second.
+    pub ki: f64,
+}
+
+/// Represents the correction built up so far and when it was last updated.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct State {
+    time: Time,
+    integral: f64,
+}
+
+/// Represents a closed-loop regulator driving the fan to a target RPM.
+///
+/// Curves and controllers keep producing fan speeds, but each speed is read as a target RPM
+/// relative to the configured maximum RPM instead of a PWM duty. The regulator starts from the
+/// target speed as its duty and corrects it until the tachometer reading matches, so fans of
+/// different models on the same curve spin at the same speed.
+#[derive(Debug, Clone)]
+pub struct RpmRegulator {
+    config: Config,
+    state: Option<State>,
+}
+
+impl RpmRegulator {
+    pub fn new(config: Config) -> Result<Self> {
+        let max_rpm = config.max_rpm.get::<revolution_per_minute>();
+        if max_rpm.is_nan() || max_rpm <= 0.0 {
+            return Err(Error::InvalidMaxRpm(max_rpm));
+        }
+        for gain in [config.kp, config.ki] {
+            if !gain.is_finite() || gain < 0.0 {
+                return Err(Error::InvalidGain(gain));
+            }
+        }
+
+        Ok(Self {
+            config,
+            state: None,
+        })
+    }
+
+    /// Returns the regulator parameters.
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the RPM that `target` stands for.
+    #[must_use]
+    pub fn target_rpm(&self, target: fan::Speed) -> AngularVelocity {
+        (self.config.max_rpm * *target).into()
+    }
+
+    /// Clears the correction built up so far.
+    ///
+    /// Call when the fan isn't being driven by the regulator, e.g. while it's stopped or being
+    /// kicked, so the correction doesn't wind up.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Returns the fan duty to apply at `now` to reach `target`.
+    ///
+    /// `rpm` is the measured fan speed, or `None` if the tachometer couldn't be read, in which
+    /// case the last correction is held.
+    pub fn update(
+        &mut self,
+        now: Time,
+        target: fan::Speed,
+        rpm: Option<AngularVelocity>,
+    ) -> Result<fan::Speed> {
+        let target = target.get::<percent>();
+        if target <= 0.0 {
+            self.reset();
+            return Ok(fan::Speed::new(Ratio::new::<percent>(0.0))?);
+        }
+
+        let integral = self.state.map_or(0.0, |state| state.integral);
+        let Some(rpm) = rpm else {
+            return Self::duty(target + integral);
+        };
+
+        let error = target
+            - 100.0 * rpm.get::<revolution_per_minute>()
+                / self.config.max_rpm.get::<revolution_per_minute>();
+        let elapsed = self
+            .state
+            .map_or(0.0, |state| (now - state.time).get::<second>().max(0.0));
+
+        let proportional = self.config.kp * error;
+        let candidate = integral + self.config.ki * error * elapsed;
+
+        // Conditional integration: stop accumulating while the output is pinned in the direction
+        // the error is pushing it, e.g. when the target is faster than the fan can spin.
+        let unclamped = target + proportional + candidate;
+        let saturated = (unclamped > 100.0 && error > 0.0) || (unclamped < 0.0 && error < 0.0);
+        let integral = if saturated { integral } else { candidate };
+
+        self.state = Some(State {
+            time: now,
+            integral,
+        });
+        Self::duty(target + proportional + integral)
+    }
+
+    fn duty(duty: f64) -> Result<fan::Speed> {
+        Ok(fan::Speed::new(Ratio::new::<percent>(
+            duty.clamp(0.0, 100.0),
+        ))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+    use crate::test_util::{rpm, speed};
+
+    fn new_regulator() -> RpmRegulator {
+        RpmRegulator::new(Config {
+            max_rpm: rpm(2000.0),
+            kp: 0.2,
+            ki: 0.3,
+        })
+        .unwrap()
+    }
+
+    /// Runs a simulated fan whose RPM is proportional to duty for `steps` one-second updates, and
+    /// returns its final RPM.
+    fn simulate(regulator: &mut RpmRegulator, fan_max_rpm: f64, target: f64, steps: u32) -> f64 {
+        let mut fan_rpm = 0.0;
+        for i in 0..steps {
+            let duty = regulator
+                .update(
+                    Time::new::<second>(f64::from(i)),
+                    speed(target),
+                    Some(rpm(fan_rpm)),
+                )
+                .unwrap();
+            fan_rpm = fan_max_rpm * duty.get::<percent>() / 100.0;
+        }
+        fan_rpm
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = Config {
+            max_rpm: rpm(0.0),
+            kp: 0.5,
+            ki: 0.5,
+        };
+        assert_eq!(
+            RpmRegulator::new(config).unwrap_err(),
+            Error::InvalidMaxRpm(0.0)
+        );
+        assert_eq!(
+            RpmRegulator::new(Config {
+                max_rpm: rpm(2000.0),
+                ki: -1.0,
+                ..config
+            })
+            .unwrap_err(),
+            Error::InvalidGain(-1.0)
+        );
+    }
+
+    #[test]
+    fn target_rpm() {
+        let regulator = new_regulator();
+        assert_float_eq!(
+            regulator
+                .target_rpm(speed(40.0))
+                .get::<revolution_per_minute>(),
+            800.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn on_target_holds_duty() {
+        let mut regulator = new_regulator();
+
+        for i in 0..3 {
+            let duty = regulator
+                .update(
+                    Time::new::<second>(f64::from(i)),
+                    speed(50.0),
+                    Some(rpm(1000.0)),
+                )
+                .unwrap();
+            assert_eq!(duty, speed(50.0));
+        }
+    }
+
+    #[test]
+    fn different_fans_reach_same_rpm() {
+        for fan_max_rpm in [1500.0, 2000.0, 3000.0] {
+            let mut regulator = new_regulator();
+            let fan_rpm = simulate(&mut regulator, fan_max_rpm, 50.0, 50);
+            assert_float_eq!(fan_rpm, 1000.0, abs <= 1.0);
+        }
+    }
+
+    #[test]
+    fn unreachable_target_does_not_wind_up() {
+        let mut regulator = new_regulator();
+
+        // The fan tops out at 1500rpm, so a 2000rpm target pins the duty at 100%.
+        assert_float_eq!(
+            simulate(&mut regulator, 1500.0, 100.0, 20),
+            1500.0,
+            abs <= 1e-9
+        );
+        // Without wind-up, the duty comes straight off the limit once the target is reachable.
+        let duty = regulator
+            .update(Time::new::<second>(20.0), speed(50.0), Some(rpm(1500.0)))
+            .unwrap();
+        assert!(duty < speed(100.0));
+    }
+
+    #[test]
+    fn missing_rpm_holds_correction() {
+        let mut regulator = new_regulator();
+        simulate(&mut regulator, 1500.0, 50.0, 50);
+
+        let duty = regulator
+            .update(Time::new::<second>(50.0), speed(50.0), None)
+            .unwrap();
+        assert_float_eq!(duty.get::<percent>(), 100.0 * 1000.0 / 1500.0, abs <= 0.1);
+    }
+
+    #[test]
+    fn zero_target_stops_and_resets() {
+        let mut regulator = new_regulator();
+        simulate(&mut regulator, 1500.0, 50.0, 50);
+
+        let duty = regulator
+            .update(Time::new::<second>(50.0), speed(0.0), Some(rpm(1000.0)))
+            .unwrap();
+        assert_eq!(duty, speed(0.0));
+        let duty = regulator
+            .update(Time::new::<second>(51.0), speed(50.0), None)
+            .unwrap();
+        assert_eq!(duty, speed(50.0));
+    }
+}
@@ -0,0 +1,189 @@
+use uom::si::{temperature_interval, thermodynamic_temperature::degree_celsius};
+
+use crate::{
+    decode::fan,
+    differential::{self, DeltaCurve},
+    fan_curve::{self, FanCurve},
+    pid::{self, Pid},
+    units::{ThermodynamicTemperature, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a fan control strategy error.
+#[derive(Debug, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The temperature the strategy needs is unavailable.
+    #[error("missing temperature reading")]
+    MissingTemperature,
+    /// A fan curve error occurred.
+    #[error("fan curve error: {0}")]
+    FanCurveError(#[from] fan_curve::Error),
+    /// A differential fan curve error occurred.
+    #[error("differential fan curve error: {0}")]
+    DifferentialError(#[from] differential::Error),
+    /// A PID controller error occurred.
+    #[error("pid error: {0}")]
+    PidError(#[from] pid::Error),
+}
+
+/// Represents the temperatures a strategy works from.
+///
+/// Temperatures are `None` when they couldn't be read.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Input {
+    /// The temperature being controlled, e.g. the hottest sensor or the exhaust.
+    pub temp: Option<ThermodynamicTemperature>,
+    /// The ambient or intake temperature.
+    pub ambient: Option<ThermodynamicTemperature>,
+}
+
+/// Represents how a fan's speed is derived from temperature.
+#[derive(Debug, Clone)]
+pub enum Strategy {
+    /// Looks the fan speed up on a fan curve.
+    Curve(FanCurve),
+    /// Looks the fan speed up on a fan curve keyed on temperature rise over ambient.
+    Differential(DeltaCurve),
+    /// Adjusts the fan speed to hold a temperature setpoint.
+    Pid(Pid),
+}
+
+impl Strategy {
+    /// Returns the fan speed for `input` measured at `now`.
+    pub fn update(&mut self, now: Time, input: Input) -> Result<fan::Speed> {
+        match self {
+            Self::Curve(curve) => Ok(curve.sample(input.temp.ok_or(Error::MissingTemperature)?)?),
+            Self::Differential(curve) => Ok(curve.sample(input.temp, input.ambient)?),
+            Self::Pid(pid) => Ok(pid.update(now, input.temp.ok_or(Error::MissingTemperature)?)?),
+        }
+    }
+
+    /// Returns what the strategy is keyed on in °C, if it's available.
+    ///
+    /// Differential strategies are keyed on the temperature rise over ambient, curves on the
+    /// temperature. Suitable for feeding hysteresis.
+    ///
+    /// PID strategies have no key, since holding back a closed-loop controller's output would wind
+    /// up its integral.
+    #[must_use]
+    pub fn key(&self, input: Input) -> Option<f64> {
+        match self {
+            Self::Curve(_) => Some(input.temp?.get::<degree_celsius>()),
+            Self::Pid(_) => None,
+            Self::Differential(_) => Some(
+                differential::delta(input.temp?, input.ambient?)
+                    .get::<temperature_interval::degree_celsius>(),
+            ),
+        }
+    }
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self::Curve(FanCurve::default())
+    }
+}
+
+impl From<FanCurve> for Strategy {
+    fn from(value: FanCurve) -> Self {
+        Self::Curve(value)
+    }
+}
+
+impl From<DeltaCurve> for Strategy {
+    fn from(value: DeltaCurve) -> Self {
+        Self::Differential(value)
+    }
+}
+
+impl From<Pid> for Strategy {
+    fn from(value: Pid) -> Self {
+        Self::Pid(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+    use uom::si::{ratio::percent, temperature_interval, time::second};
+
+    use super::*;
+    use crate::{
+        test_util::{speed, temp},
+        units::TemperatureInterval,
+    };
+
+    #[test]
+    fn curve_strategy() {
+        let mut strategy = Strategy::default();
+        let input = Input {
+            temp: Some(temp(65.0)),
+            ambient: None,
+        };
+
+        let fan_speed = strategy.update(Time::default(), input).unwrap();
+        assert_float_eq!(fan_speed.get::<percent>(), 100.0, abs <= 1e-9);
+        assert_eq!(strategy.key(input), Some(65.0));
+        assert!(matches!(
+            strategy.update(Time::default(), Input::default()),
+            Err(Error::MissingTemperature)
+        ));
+    }
+
+    #[test]
+    fn differential_strategy() {
+        let mut strategy = Strategy::from(
+            DeltaCurve::from_points([
+                (
+                    TemperatureInterval::new::<temperature_interval::degree_celsius>(0.0),
+                    speed(0.0),
+                ),
+                (
+                    TemperatureInterval::new::<temperature_interval::degree_celsius>(10.0),
+                    speed(100.0),
+                ),
+            ])
+            .unwrap(),
+        );
+        let input = Input {
+            temp: Some(temp(40.0)),
+            ambient: Some(temp(35.0)),
+        };
+
+        let fan_speed = strategy.update(Time::default(), input).unwrap();
+        assert_float_eq!(fan_speed.get::<percent>(), 50.0, abs <= 1e-9);
+        assert_eq!(strategy.key(input), Some(5.0));
+        assert_eq!(
+            strategy.key(Input {
+                ambient: None,
+                ..input
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn pid_strategy() {
+        let mut strategy = Strategy::from(
+            Pid::new(pid::Config {
+                setpoint: temp(40.0),
+                kp: 10.0,
+                ki: 0.0,
+                kd: 0.0,
+                sample_period: Time::new::<second>(1.0),
+                min_speed: speed(0.0),
+                max_speed: speed(100.0),
+            })
+            .unwrap(),
+        );
+        let input = Input {
+            temp: Some(temp(43.0)),
+            ambient: None,
+        };
+
+        let fan_speed = strategy.update(Time::default(), input).unwrap();
+        assert_float_eq!(fan_speed.get::<percent>(), 30.0, abs <= 1e-9);
+        assert_eq!(strategy.key(input), None);
+    }
+}
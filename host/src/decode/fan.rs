@@ -1,6 +1,18 @@
-use uom::si::{frequency::hertz, ratio::percent};
+use core::ops::RangeInclusive;
 
-use crate::units::{Frequency, Ratio};
+use heapless::Vec;
+use num_traits::Float;
+use uom::si::{
+    angular_velocity::revolution_per_minute,
+    frequency::hertz,
+    ratio::percent,
+    time::{millisecond, second},
+};
+
+use crate::{
+    pwm,
+    units::{AngularVelocity, Frequency, Ratio, Time},
+};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -10,17 +22,101 @@ pub enum Error {
     /// The checksum is mismatched.
     #[error("invalid fan speed: expected 0≤x≤100%, got {0}%")]
     InvalidSpeed(f64),
+    /// Too few tachometer pulses were counted to measure the fan speed.
     #[error("not enough samples: expected x≥2, got {0} samples")]
     NotEnoughSamples(usize),
+    /// The number of tachometer pulses per revolution is zero.
+    #[error("invalid pulses per revolution: expected x≥1, got {0}")]
+    InvalidPulsesPerRevolution(u8),
+    /// The tachometer measurement window isn't positive.
+    #[error("invalid measurement window: expected x>0s, got {0}s")]
+    InvalidWindow(f64),
+    /// A tachometer pulse period isn't positive.
+    #[error("invalid pulse period: expected x>0s, got {0}s")]
+    InvalidPeriod(f64),
+    /// More tachometer pulse periods were provided than can be averaged.
+    #[error("too many samples: expected x≤{0} samples")]
+    TooManySamples(usize),
+    /// The low-frequency PWM frequency is outside the supported range.
+    #[error("invalid drive frequency: expected 20Hz≤x≤160Hz, got {0}Hz")]
+    InvalidDriveFrequency(f64),
+    /// The fan PWM signal can't be produced.
+    #[error("pwm error: {0}")]
+    PwmError(#[from] pwm::Error),
+}
+
+/// Default number of tachometer pulses per revolution.
+///
+/// As specified by Intel "4-Wire Pulse Width Modulation (PWM) Controlled Fans".
+pub const DEFAULT_PULSES_PER_REVOLUTION: u8 = 2;
+
+/// Maximum number of tachometer pulse periods averaged in one measurement.
+pub const MAX_PERIODS: usize = 16;
+
+/// Supported frequency range for low-frequency PWM, in Hz.
+pub const LOW_FREQUENCY_RANGE: RangeInclusive<f64> = 20.0..=160.0;
+
+/// Represents a low-frequency PWM frequency within [`LOW_FREQUENCY_RANGE`].
+///
+/// Only created through [`DriveMode::low_frequency`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LowFrequency(Frequency);
+
+impl LowFrequency {
+    #[must_use]
+    pub fn get(self) -> Frequency {
+        self.0
+    }
+}
+
+/// Represents how a fan is driven.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum DriveMode {
+    /// 25kHz PWM on the fan's control wire, as specified by Intel "4-Wire Pulse Width Modulation
+    /// (PWM) Controlled Fans".
+    #[default]
+    FourWire,
+    /// Low-frequency PWM switching the supply of a 3-pin fan, e.g. through a MOSFET.
+    ///
+    /// The fan's tachometer is unpowered, and so meaningless, while the supply is off.
+    LowFrequency(LowFrequency),
+}
+
+impl DriveMode {
+    /// Creates a low-frequency PWM drive mode.
+    pub fn low_frequency(frequency: Frequency) -> Result<Self> {
+        let hz = frequency.get::<hertz>();
+        if !LOW_FREQUENCY_RANGE.contains(&hz) {
+            return Err(Error::InvalidDriveFrequency(hz));
+        }
+        Ok(Self::LowFrequency(LowFrequency(frequency)))
+    }
+
+    /// Returns the PWM frequency.
+    #[must_use]
+    pub fn frequency(&self) -> Frequency {
+        match self {
+            Self::FourWire => Frequency::new::<hertz>(25_000.0),
+            Self::LowFrequency(frequency) => frequency.get(),
+        }
+    }
+
+    /// Returns `true` if tachometer readings are only valid during the on-phase of the PWM
+    /// signal.
+    #[must_use]
+    pub fn is_tach_gated(&self) -> bool {
+        matches!(self, Self::LowFrequency(_))
+    }
 }
 
 /// Represents desired fan speed.
-#[derive(Default, derive_more::Deref)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, PartialOrd, derive_more::Deref)]
 pub struct Speed(Ratio);
 
 /// Represents RP2040 PWM parameters.
 #[derive(Debug, Copy, Clone, PartialEq, defmt::Format)]
 pub struct RpPwmConfig {
+    pub divider: pwm::Divider,
     pub top: u16,
     pub compare: u16,
 }
@@ -36,55 +132,423 @@ impl Speed {
         }
     }
 
+    /// Returns the fan speed at full duty.
     #[must_use]
-    pub fn pwm_config(&self, clock: Frequency) -> RpPwmConfig {
-        // As specified by Intel "4-Wire Pulse Width Modulation (PWM) Controlled Fans".
-        let fan_pwm_signal = Frequency::new::<hertz>(25_000.0);
-        let fan_speed = self.0;
-        let top = clock / fan_pwm_signal;
-        let compare = top * fan_speed;
-
-        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
-        RpPwmConfig {
-            top: top.value as u16,
-            compare: compare.value as u16,
+    pub fn full() -> Self {
+        Self(Ratio::new::<percent>(100.0))
+    }
+
+    pub fn pwm_config(&self, clock: Frequency, mode: DriveMode) -> Result<RpPwmConfig> {
+        let solution = pwm::Solution::new(clock, mode.frequency())?;
+        let compare = solution.compare(*self);
+
+        // The RP2040 counts tachometer edges on the slice through the same divider, so a divided
+        // slice would only count every n-th pulse. A fully off or fully on output doesn't depend
+        // on the divider, so run those undivided, e.g. while a gated tachometer is measured.
+        let constant = compare == 0 || u32::from(compare) > u32::from(solution.top);
+        let divider = if constant {
+            pwm::Divider { int: 1, frac: 0 }
+        } else {
+            solution.divider
+        };
+
+        Ok(RpPwmConfig {
+            divider,
+            top: solution.top,
+            compare,
+        })
+    }
+}
+
+/// Represents a fan tachometer signal decoder.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub struct Tachometer {
+    pulses_per_revolution: u8,
+}
+
+impl Tachometer {
+    pub fn new(pulses_per_revolution: u8) -> Result<Self> {
+        if pulses_per_revolution == 0 {
+            return Err(Error::InvalidPulsesPerRevolution(pulses_per_revolution));
+        }
+
+        Ok(Self {
+            pulses_per_revolution,
+        })
+    }
+
+    #[must_use]
+    pub fn pulses_per_revolution(&self) -> u8 {
+        self.pulses_per_revolution
+    }
+
+    /// Returns the fan speed from the number of `pulses` counted over `window`.
+    ///
+    /// No pulses means the fan is stopped. A single pulse is too coarse to measure a speed from,
+    /// since it could be anywhere between just under one and just over two pulse periods.
+    pub fn rpm_from_count(&self, pulses: u16, window: Time) -> Result<AngularVelocity> {
+        let window = window.get::<second>();
+        if window.is_nan() || window <= 0.0 {
+            return Err(Error::InvalidWindow(window));
+        }
+
+        match pulses {
+            0 => Ok(AngularVelocity::new::<revolution_per_minute>(0.0)),
+            1 => Err(Error::NotEnoughSamples(usize::from(pulses))),
+            _ => {
+                let revolutions = f64::from(pulses) / f64::from(self.pulses_per_revolution);
+                Ok(AngularVelocity::new::<revolution_per_minute>(
+                    revolutions / window * 60.0,
+                ))
+            }
+        }
+    }
+
+    /// Returns the fan speed from the `period` between two consecutive pulses.
+    pub fn rpm_from_period(&self, period: Time) -> Result<AngularVelocity> {
+        let period = period.get::<second>();
+        if period.is_nan() || period <= 0.0 {
+            return Err(Error::InvalidPeriod(period));
+        }
+
+        let revolution = period * f64::from(self.pulses_per_revolution);
+        Ok(AngularVelocity::new::<revolution_per_minute>(
+            60.0 / revolution,
+        ))
+    }
+}
+
+/// Represents period-based tachometer measurement parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PeriodConfig {
+    /// The longest pulse period before the fan counts as stalled. Also bounds how long a
+    /// measurement takes.
+    pub timeout: Time,
+    /// How far a period may stray from the median period before it's rejected, e.g. because of
+    /// a noise edge or a missed edge.
+    pub outlier_tolerance: Ratio,
+}
+
+impl Default for PeriodConfig {
+    /// Creates a measurement that counts fans below 60rpm at 2 pulses per revolution as stalled.
+    fn default() -> Self {
+        Self {
+            timeout: Time::new::<millisecond>(500.0),
+            outlier_tolerance: Ratio::new::<percent>(25.0),
+        }
+    }
+}
+
+impl Tachometer {
+    /// Returns the fan speed from the `periods` between consecutive pulses.
+    ///
+    /// Periods too far from the median period are rejected and the rest are averaged. If every
+    /// period is rejected, e.g. because the fan changed speed mid-measurement, the median is used.
+    /// A period at or beyond the timeout, e.g. because no edge arrived in time, means the fan
+    /// stalled.
+    pub fn rpm_from_periods(
+        &self,
+        periods: &[Time],
+        config: &PeriodConfig,
+    ) -> Result<AngularVelocity> {
+        if periods.is_empty() {
+            return Err(Error::NotEnoughSamples(0));
+        }
+
+        let mut sorted = Vec::<f64, MAX_PERIODS>::new();
+        for period in periods {
+            let period = period.get::<second>();
+            if period.is_nan() || period <= 0.0 {
+                return Err(Error::InvalidPeriod(period));
+            }
+            if period >= config.timeout.get::<second>() {
+                return Ok(AngularVelocity::new::<revolution_per_minute>(0.0));
+            }
+            sorted
+                .push(period)
+                .map_err(|_| Error::TooManySamples(MAX_PERIODS))?;
+        }
+        sorted.sort_unstable_by(f64::total_cmp);
+
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+
+        let tolerance = median * config.outlier_tolerance.get::<percent>() / 100.0;
+        let (sum, count) = sorted
+            .iter()
+            .filter(|&&period| Float::abs(period - median) <= tolerance)
+            .fold((0.0, 0.0), |(sum, count), period| {
+                (sum + period, count + 1.0)
+            });
+
+        let period = if count > 0.0 { sum / count } else { median };
+        self.rpm_from_period(Time::new::<second>(period))
+    }
+}
+
+impl Default for Tachometer {
+    fn default() -> Self {
+        Self {
+            pulses_per_revolution: DEFAULT_PULSES_PER_REVOLUTION,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use float_eq::assert_float_eq;
     use uom::si::{
         f64::{Frequency, Ratio},
         ratio::percent,
+        time::millisecond,
     };
 
     use super::*;
 
+    fn rpm(tachometer: Tachometer, pulses: u16, window: f64) -> Result<f64> {
+        tachometer
+            .rpm_from_count(pulses, Time::new::<millisecond>(window))
+            .map(|rpm| rpm.get::<revolution_per_minute>())
+    }
+
+    #[test]
+    fn tach_count_to_rpm() {
+        let tachometer = Tachometer::default();
+        // 20 pulses in 500ms is 10 revolutions in 500ms.
+        assert_float_eq!(rpm(tachometer, 20, 500.0).unwrap(), 1200.0, abs <= 1e-9);
+        assert_float_eq!(rpm(tachometer, 100, 1000.0).unwrap(), 3000.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn tach_pulses_per_revolution() {
+        let tachometer = Tachometer::new(4).unwrap();
+        assert_eq!(tachometer.pulses_per_revolution(), 4);
+        assert_float_eq!(rpm(tachometer, 20, 500.0).unwrap(), 600.0, abs <= 1e-9);
+
+        assert_eq!(
+            Tachometer::new(0).unwrap_err(),
+            Error::InvalidPulsesPerRevolution(0)
+        );
+    }
+
+    #[test]
+    fn tach_low_count() {
+        let tachometer = Tachometer::default();
+        assert_eq!(
+            rpm(tachometer, 1, 500.0).unwrap_err(),
+            Error::NotEnoughSamples(1)
+        );
+        // The lowest measurable speed over a 500ms window.
+        assert_float_eq!(rpm(tachometer, 2, 500.0).unwrap(), 120.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn tach_zero_count() {
+        let tachometer = Tachometer::default();
+        assert_float_eq!(rpm(tachometer, 0, 500.0).unwrap(), 0.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn tach_invalid_window() {
+        let tachometer = Tachometer::default();
+        assert_eq!(
+            rpm(tachometer, 20, 0.0).unwrap_err(),
+            Error::InvalidWindow(0.0)
+        );
+    }
+
+    fn periods(periods: &[f64]) -> Vec<Time, MAX_PERIODS> {
+        periods
+            .iter()
+            .map(|&period| Time::new::<millisecond>(period))
+            .collect()
+    }
+
+    fn rpm_from_periods(periods_ms: &[f64]) -> Result<f64> {
+        Tachometer::default()
+            .rpm_from_periods(&periods(periods_ms), &PeriodConfig::default())
+            .map(|rpm| rpm.get::<revolution_per_minute>())
+    }
+
+    #[test]
+    fn tach_periods_are_averaged() {
+        // ~200rpm, far too slow to count accurately over a short window.
+        assert_float_eq!(
+            rpm_from_periods(&[149.0, 151.0, 150.0, 150.0]).unwrap(),
+            200.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn tach_period_outliers_are_rejected() {
+        // A noise edge splits one period in two, and a missed edge doubles another.
+        assert_float_eq!(
+            rpm_from_periods(&[150.0, 40.0, 110.0, 150.0, 300.0, 150.0]).unwrap(),
+            200.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn tach_all_periods_rejected() {
+        // The median of 200ms is 150rpm, and neither period is within 25% of it.
+        assert_float_eq!(
+            rpm_from_periods(&[100.0, 300.0]).unwrap(),
+            150.0,
+            abs <= 1e-9
+        );
+        assert_float_eq!(
+            rpm_from_periods(&[100.0, 100.0, 300.0, 300.0]).unwrap(),
+            150.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn tach_period_timeout_is_stalled() {
+        assert_float_eq!(rpm_from_periods(&[150.0, 500.0]).unwrap(), 0.0, abs <= 1e-9);
+        assert_float_eq!(rpm_from_periods(&[600.0]).unwrap(), 0.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn tach_invalid_periods() {
+        assert_eq!(
+            rpm_from_periods(&[]).unwrap_err(),
+            Error::NotEnoughSamples(0)
+        );
+        assert_eq!(
+            rpm_from_periods(&[150.0, 0.0]).unwrap_err(),
+            Error::InvalidPeriod(0.0)
+        );
+        assert_eq!(
+            Tachometer::default()
+                .rpm_from_periods(
+                    &[Time::new::<millisecond>(10.0); 17],
+                    &PeriodConfig::default()
+                )
+                .unwrap_err(),
+            Error::TooManySamples(MAX_PERIODS)
+        );
+    }
+
+    #[test]
+    fn tach_period_to_rpm() {
+        let tachometer = Tachometer::default();
+        // 2 pulses per revolution 25ms apart is one revolution every 50ms.
+        assert_float_eq!(
+            tachometer
+                .rpm_from_period(Time::new::<millisecond>(25.0))
+                .unwrap()
+                .get::<revolution_per_minute>(),
+            1200.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn tach_invalid_period() {
+        let tachometer = Tachometer::default();
+        assert_eq!(
+            tachometer
+                .rpm_from_period(Time::new::<millisecond>(0.0))
+                .unwrap_err(),
+            Error::InvalidPeriod(0.0)
+        );
+        assert_eq!(
+            tachometer
+                .rpm_from_period(Time::new::<millisecond>(-25.0))
+                .unwrap_err(),
+            Error::InvalidPeriod(-0.025)
+        );
+        assert!(matches!(
+            tachometer.rpm_from_period(Time::new::<millisecond>(f64::NAN)),
+            Err(Error::InvalidPeriod(period)) if period.is_nan()
+        ));
+    }
+
     #[test]
     fn fan_speed_to_pwm_config() {
+        let config = |speed| {
+            Speed(Ratio::new::<percent>(speed))
+                .pwm_config(Frequency::new::<hertz>(125_000_000.0), DriveMode::default())
+                .unwrap()
+        };
+        let divider = pwm::Divider { int: 1, frac: 0 };
+
         assert_eq!(
-            Speed(Ratio::new::<percent>(0.0)).pwm_config(Frequency::new::<hertz>(125_000_000.0)),
+            config(0.0),
             RpPwmConfig {
-                top: 5000,
+                divider,
+                top: 4999,
                 compare: 0,
             }
         );
 
         assert_eq!(
-            Speed(Ratio::new::<percent>(50.0)).pwm_config(Frequency::new::<hertz>(125_000_000.0)),
+            config(50.0),
             RpPwmConfig {
-                top: 5000,
+                divider,
+                top: 4999,
                 compare: 2500,
             }
         );
 
         assert_eq!(
-            Speed(Ratio::new::<percent>(100.0)).pwm_config(Frequency::new::<hertz>(125_000_000.0)),
+            config(100.0),
             RpPwmConfig {
-                top: 5000,
+                divider,
+                top: 4999,
                 compare: 5000,
             }
         );
     }
+
+    #[test]
+    fn unreachable_pwm_frequency() {
+        assert!(matches!(
+            Speed(Ratio::new::<percent>(50.0))
+                .pwm_config(Frequency::new::<hertz>(10_000.0), DriveMode::default()),
+            Err(Error::PwmError(_))
+        ));
+    }
+
+    #[test]
+    fn low_frequency_pwm_config() {
+        let mode = DriveMode::low_frequency(Frequency::new::<hertz>(25.0)).unwrap();
+        assert!(mode.is_tach_gated());
+        assert!(!DriveMode::FourWire.is_tach_gated());
+
+        let config = Speed(Ratio::new::<percent>(50.0))
+            .pwm_config(Frequency::new::<hertz>(125_000_000.0), mode)
+            .unwrap();
+        // 125MHz / 25Hz needs a divider of at least 76.3.
+        assert!(config.divider.int >= 76);
+        assert_eq!(u32::from(config.compare) * 2, u32::from(config.top) + 1);
+    }
+
+    #[test]
+    fn constant_outputs_are_undivided() {
+        let mode = DriveMode::low_frequency(Frequency::new::<hertz>(25.0)).unwrap();
+        let undivided = pwm::Divider { int: 1, frac: 0 };
+
+        for speed in [0.0, 100.0] {
+            let config = Speed(Ratio::new::<percent>(speed))
+                .pwm_config(Frequency::new::<hertz>(125_000_000.0), mode)
+                .unwrap();
+            assert_eq!(config.divider, undivided);
+        }
+    }
+
+    #[test]
+    fn invalid_drive_frequency() {
+        assert_eq!(
+            DriveMode::low_frequency(Frequency::new::<hertz>(1000.0)).unwrap_err(),
+            Error::InvalidDriveFrequency(1000.0)
+        );
+    }
 }
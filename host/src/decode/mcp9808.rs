@@ -2,9 +2,10 @@ use core::ops::RangeInclusive;
 
 use bitvec::prelude::*;
 use fixed::FixedI16;
-use uom::si::thermodynamic_temperature::degree_celsius;
+use num_traits::Float;
+use uom::si::{thermodynamic_temperature::degree_celsius, time::millisecond};
 
-use crate::units::ThermodynamicTemperature;
+use crate::units::{ThermodynamicTemperature, Time};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -13,10 +14,30 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     #[error("invalid temperature: expected –40°C≤x≤125°C, got {0}°C")]
     InvalidTemperature(f64),
+    #[error("invalid limit: expected a multiple of 0.25°C, got {0}°C")]
+    InvalidLimit(f64),
+    #[error("invalid address: expected 0x18≤x≤0x1F, got {0:#04X}")]
+    InvalidAddress(u16),
 }
 
-#[derive(Debug, PartialEq, Eq, derive_more::Deref, defmt::Format)]
-pub struct Address(pub u16);
+/// Addresses a MCP9808 can be strapped to with its A0–A2 pins.
+///
+/// See: datasheet table 3-2, page 11.
+pub const ADDRESS_RANGE: RangeInclusive<u16> = 0x18..=0x1F;
+
+/// Represents a MCP9808 I2C address, always within [`ADDRESS_RANGE`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, derive_more::Deref, defmt::Format)]
+pub struct Address(u16);
+
+impl Address {
+    pub const fn new(address: u16) -> Result<Self> {
+        if *ADDRESS_RANGE.start() <= address && address <= *ADDRESS_RANGE.end() {
+            Ok(Self(address))
+        } else {
+            Err(Error::InvalidAddress(address))
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq, derive_more::Deref, defmt::Format)]
 pub struct ManufacturerId(pub u16);
@@ -27,7 +48,125 @@ pub struct DeviceId(pub u8);
 #[derive(Debug, PartialEq, Eq, derive_more::Deref, defmt::Format)]
 pub struct Revision(pub u8);
 
+/// Represents a MCP9808 ambient temperature reading.
+///
+/// See: datasheet § 5.1.3, page 24.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TemperatureReading {
+    pub temp: ThermodynamicTemperature,
+    /// The temperature is at or above `T_CRIT`.
+    pub critical: bool,
+    /// The temperature is above `T_UPPER`.
+    pub above_upper: bool,
+    /// The temperature is below `T_LOWER`.
+    pub below_lower: bool,
+}
+
+/// Represents the MCP9808 alert output hysteresis, applied to all temperature limits.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Hysteresis {
+    /// 0°C.
+    #[default]
+    Zero,
+    /// +1.5°C.
+    OnePointFive,
+    /// +3.0°C.
+    Three,
+    /// +6.0°C.
+    Six,
+}
+
+/// Represents which limits trigger the MCP9808 alert output.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum AlertSelect {
+    /// `T_UPPER`, `T_LOWER` and `T_CRIT`.
+    #[default]
+    AllLimits,
+    /// `T_CRIT` only.
+    CriticalOnly,
+}
+
+/// Represents the MCP9808 alert output polarity.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum AlertPolarity {
+    #[default]
+    ActiveLow,
+    ActiveHigh,
+}
+
+/// Represents how the MCP9808 alert output is asserted.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum AlertMode {
+    /// Asserted for as long as a limit is exceeded.
+    #[default]
+    Comparator,
+    /// Asserted when a limit is crossed, until the interrupt is cleared.
+    Interrupt,
+}
+
+/// Represents the MCP9808 configuration register.
+///
+/// The default is the power-on configuration.
+///
+/// See: datasheet § 5.1.1, page 18.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub struct Config {
+    pub hysteresis: Hysteresis,
+    /// Stops temperature conversions to save power.
+    pub shutdown: bool,
+    /// Locks `T_CRIT` against writes until the next power cycle.
+    pub critical_lock: bool,
+    /// Locks `T_UPPER` and `T_LOWER` against writes until the next power cycle.
+    pub window_lock: bool,
+    /// Clears an interrupt. Always reads as `false`.
+    pub interrupt_clear: bool,
+    /// The alert output is asserted. Read-only.
+    pub alert_asserted: bool,
+    /// Enables the alert output.
+    pub alert_enabled: bool,
+    pub alert_select: AlertSelect,
+    pub alert_polarity: AlertPolarity,
+    pub alert_mode: AlertMode,
+}
+
+/// Represents the MCP9808 temperature resolution.
+///
+/// The default is the power-on resolution.
+///
+/// See: datasheet § 5.1.6, page 29.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Resolution {
+    /// 0.5°C.
+    Half,
+    /// 0.25°C.
+    Quarter,
+    /// 0.125°C.
+    Eighth,
+    /// 0.0625°C.
+    #[default]
+    Sixteenth,
+}
+
+impl Resolution {
+    /// Returns how long the sensor takes to convert a temperature at this resolution.
+    ///
+    /// See: datasheet table 1-1, page 3.
+    #[must_use]
+    pub fn conversion_time(self) -> Time {
+        Time::new::<millisecond>(match self {
+            Self::Half => 30.0,
+            Self::Quarter => 65.0,
+            Self::Eighth => 130.0,
+            Self::Sixteenth => 250.0,
+        })
+    }
+}
+
+pub type ConfigPayload = BitArray<[u8; 2], Msb0>;
+pub type ResolutionPayload = BitArray<[u8; 1], Msb0>;
 pub type TemperaturePayload = BitArray<[u8; 2], Msb0>;
+pub type LimitPayload = BitArray<[u8; 2], Msb0>;
 pub type ManufacturerIdPayload = BitArray<[u8; 2], Msb0>;
 pub type DeviceIdPayload = BitArray<[u8; 2], Msb0>;
 
@@ -35,18 +174,159 @@ pub mod raw {
     use super::*;
 
     pub type Temperature = FixedI16<4>;
+    pub type Limit = FixedI16<2>;
+
+    /// Sign and numeric bits of a temperature payload, as a 13-bit two's complement value in
+    /// sixteenths of a degree. The remaining bits are alert flags.
+    const TEMPERATURE_BITS: RangeInclusive<usize> = 3..=15;
 
     /// Decodes a MCP9808 temperature payload.
     ///
     /// See: datasheet § 5.1.3, page 24.
     pub fn decode_temperature(payload: TemperaturePayload) -> Result<Temperature> {
-        const SIGN_BIT: usize = 3;
-        const NUMERIC_BITS: RangeInclusive<usize> = 4..=15;
+        // Loading into a signed integer sign-extends, which is equivalent to the datasheet's
+        // `(UpperByte × 16 + LowerByte / 16) − 256` for sub-zero temperatures.
+        Ok(Temperature::from_bits(
+            payload[TEMPERATURE_BITS].load_be::<i16>(),
+        ))
+    }
 
-        let sign = if payload[SIGN_BIT] { -1 } else { 1 };
-        let mut bits = TemperaturePayload::ZERO;
-        bits[NUMERIC_BITS].copy_from_bitslice(&payload[NUMERIC_BITS]);
-        Ok(Temperature::from_be_bytes(bits.into_inner()) * sign)
+    /// Encodes a MCP9808 temperature payload, with all alert flags clear.
+    ///
+    /// See: datasheet § 5.1.3, page 24.
+    #[must_use]
+    pub fn encode_temperature(temp: Temperature) -> TemperaturePayload {
+        let mut payload = TemperaturePayload::ZERO;
+        payload[TEMPERATURE_BITS].store_be::<i16>(temp.to_bits());
+        payload
+    }
+
+    /// Sign and numeric bits of a limit payload, as an 11-bit two's complement value in quarters
+    /// of a degree. The remaining bits are unimplemented.
+    const LIMIT_BITS: RangeInclusive<usize> = 3..=13;
+
+    /// Decodes a MCP9808 `T_UPPER`, `T_LOWER` or `T_CRIT` limit payload.
+    ///
+    /// See: datasheet § 5.1.2, page 22.
+    pub fn decode_limit(payload: LimitPayload) -> Result<Limit> {
+        Ok(Limit::from_bits(payload[LIMIT_BITS].load_be::<i16>()))
+    }
+
+    /// Encodes a MCP9808 `T_UPPER`, `T_LOWER` or `T_CRIT` limit payload.
+    ///
+    /// See: datasheet § 5.1.2, page 22.
+    #[must_use]
+    pub fn encode_limit(limit: Limit) -> LimitPayload {
+        let mut payload = LimitPayload::ZERO;
+        payload[LIMIT_BITS].store_be::<i16>(limit.to_bits());
+        payload
+    }
+
+    /// Bit indices of the configuration register fields. Bits 15 to 11 are unimplemented.
+    const HYSTERESIS_BITS: RangeInclusive<usize> = 5..=6;
+    const SHUTDOWN_BIT: usize = 7;
+    const CRITICAL_LOCK_BIT: usize = 8;
+    const WINDOW_LOCK_BIT: usize = 9;
+    const INTERRUPT_CLEAR_BIT: usize = 10;
+    const ALERT_STATUS_BIT: usize = 11;
+    const ALERT_CONTROL_BIT: usize = 12;
+    const ALERT_SELECT_BIT: usize = 13;
+    const ALERT_POLARITY_BIT: usize = 14;
+    const ALERT_MODE_BIT: usize = 15;
+
+    /// Decodes a MCP9808 configuration payload. Unimplemented bits are ignored.
+    ///
+    /// See: datasheet § 5.1.1, page 18.
+    pub fn decode_config(payload: ConfigPayload) -> Result<Config> {
+        Ok(Config {
+            hysteresis: match payload[HYSTERESIS_BITS].load_be::<u8>() {
+                0b00 => Hysteresis::Zero,
+                0b01 => Hysteresis::OnePointFive,
+                0b10 => Hysteresis::Three,
+                _ => Hysteresis::Six,
+            },
+            shutdown: payload[SHUTDOWN_BIT],
+            critical_lock: payload[CRITICAL_LOCK_BIT],
+            window_lock: payload[WINDOW_LOCK_BIT],
+            interrupt_clear: payload[INTERRUPT_CLEAR_BIT],
+            alert_asserted: payload[ALERT_STATUS_BIT],
+            alert_enabled: payload[ALERT_CONTROL_BIT],
+            alert_select: if payload[ALERT_SELECT_BIT] {
+                AlertSelect::CriticalOnly
+            } else {
+                AlertSelect::AllLimits
+            },
+            alert_polarity: if payload[ALERT_POLARITY_BIT] {
+                AlertPolarity::ActiveHigh
+            } else {
+                AlertPolarity::ActiveLow
+            },
+            alert_mode: if payload[ALERT_MODE_BIT] {
+                AlertMode::Interrupt
+            } else {
+                AlertMode::Comparator
+            },
+        })
+    }
+
+    /// Encodes a MCP9808 configuration payload.
+    ///
+    /// See: datasheet § 5.1.1, page 18.
+    #[must_use]
+    pub fn encode_config(config: Config) -> ConfigPayload {
+        let mut payload = ConfigPayload::ZERO;
+        payload[HYSTERESIS_BITS].store_be::<u8>(match config.hysteresis {
+            Hysteresis::Zero => 0b00,
+            Hysteresis::OnePointFive => 0b01,
+            Hysteresis::Three => 0b10,
+            Hysteresis::Six => 0b11,
+        });
+        payload.set(SHUTDOWN_BIT, config.shutdown);
+        payload.set(CRITICAL_LOCK_BIT, config.critical_lock);
+        payload.set(WINDOW_LOCK_BIT, config.window_lock);
+        payload.set(INTERRUPT_CLEAR_BIT, config.interrupt_clear);
+        payload.set(ALERT_STATUS_BIT, config.alert_asserted);
+        payload.set(ALERT_CONTROL_BIT, config.alert_enabled);
+        payload.set(
+            ALERT_SELECT_BIT,
+            config.alert_select == AlertSelect::CriticalOnly,
+        );
+        payload.set(
+            ALERT_POLARITY_BIT,
+            config.alert_polarity == AlertPolarity::ActiveHigh,
+        );
+        payload.set(ALERT_MODE_BIT, config.alert_mode == AlertMode::Interrupt);
+        payload
+    }
+
+    /// Resolution bits of a resolution payload. The remaining bits are unimplemented.
+    const RESOLUTION_BITS: RangeInclusive<usize> = 6..=7;
+
+    /// Decodes a MCP9808 resolution payload. Unimplemented bits are ignored.
+    ///
+    /// See: datasheet § 5.1.6, page 29.
+    pub fn decode_resolution(payload: ResolutionPayload) -> Result<Resolution> {
+        Ok(match payload[RESOLUTION_BITS].load_be::<u8>() {
+            0b00 => Resolution::Half,
+            0b01 => Resolution::Quarter,
+            0b10 => Resolution::Eighth,
+            _ => Resolution::Sixteenth,
+        })
+    }
+
+    /// Encodes a MCP9808 resolution payload.
+    ///
+    /// See: datasheet § 5.1.6, page 29.
+    #[must_use]
+    pub fn encode_resolution(resolution: Resolution) -> ResolutionPayload {
+        let mut payload = ResolutionPayload::ZERO;
+        payload[RESOLUTION_BITS].store_be::<u8>(match resolution {
+            Resolution::Half => 0b00,
+            Resolution::Quarter => 0b01,
+            Resolution::Eighth => 0b10,
+            Resolution::Sixteenth => 0b11,
+        });
+        payload
     }
 
     /// Decodes a MCP9808 manufacturer ID payload.
@@ -78,6 +358,87 @@ pub fn decode_temperature(payload: TemperaturePayload) -> Result<ThermodynamicTe
     Ok(ThermodynamicTemperature::new::<degree_celsius>(temp))
 }
 
+/// Decodes a MCP9808 temperature payload along with its alert flags.
+pub fn decode_temperature_reading(payload: TemperaturePayload) -> Result<TemperatureReading> {
+    const CRITICAL_BIT: usize = 0;
+    const ABOVE_UPPER_BIT: usize = 1;
+    const BELOW_LOWER_BIT: usize = 2;
+
+    Ok(TemperatureReading {
+        temp: decode_temperature(payload)?,
+        critical: payload[CRITICAL_BIT],
+        above_upper: payload[ABOVE_UPPER_BIT],
+        below_lower: payload[BELOW_LOWER_BIT],
+    })
+}
+
+/// Encodes a MCP9808 temperature payload, rounded to the nearest 0.0625°C.
+pub fn encode_temperature(temp: ThermodynamicTemperature) -> Result<TemperaturePayload> {
+    let temp = temp.get::<degree_celsius>();
+
+    if !(-40.0..=125.0).contains(&temp) {
+        return Err(Error::InvalidTemperature(temp));
+    }
+
+    Ok(raw::encode_temperature(raw::Temperature::from_num(
+        Float::round(temp * 16.0) / 16.0,
+    )))
+}
+
+/// Decodes a MCP9808 `T_UPPER`, `T_LOWER` or `T_CRIT` limit payload.
+pub fn decode_limit(payload: LimitPayload) -> Result<ThermodynamicTemperature> {
+    let temp = raw::decode_limit(payload)?.to_num::<f64>();
+
+    if !(-40.0..=125.0).contains(&temp) {
+        return Err(Error::InvalidTemperature(temp));
+    }
+
+    Ok(ThermodynamicTemperature::new::<degree_celsius>(temp))
+}
+
+/// Encodes a MCP9808 `T_UPPER`, `T_LOWER` or `T_CRIT` limit payload.
+///
+/// Limits have a resolution of 0.25°C. Other values are rejected rather than rounded, so that a
+/// limit is never silently moved.
+pub fn encode_limit(temp: ThermodynamicTemperature) -> Result<LimitPayload> {
+    // Tolerates the error from converting to and from kelvin.
+    const EPSILON: f64 = 1e-9;
+
+    let temp = temp.get::<degree_celsius>();
+
+    if !(-40.0..=125.0).contains(&temp) {
+        return Err(Error::InvalidTemperature(temp));
+    }
+    let quarters = Float::round(temp * 4.0);
+    if Float::abs(temp * 4.0 - quarters) > EPSILON {
+        return Err(Error::InvalidLimit(temp));
+    }
+
+    Ok(raw::encode_limit(raw::Limit::from_num(quarters / 4.0)))
+}
+
+/// Decodes a MCP9808 configuration payload.
+pub fn decode_config(payload: ConfigPayload) -> Result<Config> {
+    raw::decode_config(payload)
+}
+
+/// Encodes a MCP9808 configuration payload.
+#[must_use]
+pub fn encode_config(config: Config) -> ConfigPayload {
+    raw::encode_config(config)
+}
+
+/// Decodes a MCP9808 resolution payload.
+pub fn decode_resolution(payload: ResolutionPayload) -> Result<Resolution> {
+    raw::decode_resolution(payload)
+}
+
+/// Encodes a MCP9808 resolution payload.
+#[must_use]
+pub fn encode_resolution(resolution: Resolution) -> ResolutionPayload {
+    raw::encode_resolution(resolution)
+}
+
 /// Decodes a MCP9808 manufacturer ID payload.
 pub fn decode_manufacturer_id(payload: TemperaturePayload) -> Result<ManufacturerId> {
     raw::decode_manufacturer_id(payload)
@@ -93,7 +454,9 @@ mod tests {
     use super::*;
     mod temp {
         use super::*;
-        use crate::decode::mcp9808::{decode_temperature, TemperaturePayload};
+        use crate::decode::mcp9808::{
+            decode_temperature, decode_temperature_reading, encode_temperature, TemperaturePayload,
+        };
 
         #[test]
         fn valid_range() {
@@ -103,8 +466,53 @@ mod tests {
 
         #[test]
         fn invalid_range() {
-            let temp = decode_temperature(TemperaturePayload::from([0x1F, 0xF0]));
+            // +127°C.
+            let temp = decode_temperature(TemperaturePayload::from([0x07, 0xF0]));
             assert!(temp.is_err());
+            // −45°C.
+            let temp = decode_temperature(TemperaturePayload::from([0x1D, 0x30]));
+            assert!(temp.is_err());
+
+            let temp = encode_temperature(ThermodynamicTemperature::new::<degree_celsius>(-41.0));
+            assert!(temp.is_err());
+        }
+
+        #[test]
+        fn alert_flags() {
+            let reading = |payload| {
+                let reading =
+                    decode_temperature_reading(TemperaturePayload::from(payload)).unwrap();
+                (reading.critical, reading.above_upper, reading.below_lower)
+            };
+            assert_eq!(reading([0x01, 0x94]), (false, false, false));
+            assert_eq!(reading([0x81, 0x94]), (true, false, false));
+            assert_eq!(reading([0x41, 0x94]), (false, true, false));
+            assert_eq!(reading([0x21, 0x94]), (false, false, true));
+            assert_eq!(reading([0xC1, 0x94]), (true, true, false));
+
+            let reading =
+                decode_temperature_reading(TemperaturePayload::from([0xFF, 0xF8])).unwrap();
+            assert_eq!(
+                reading.temp,
+                ThermodynamicTemperature::new::<degree_celsius>(-0.5)
+            );
+        }
+
+        #[test]
+        fn round_trip() {
+            for sixteenths in -40 * 16..=125 * 16 {
+                let temp =
+                    ThermodynamicTemperature::new::<degree_celsius>(f64::from(sixteenths) / 16.0);
+                let payload = encode_temperature(temp).unwrap();
+                assert_eq!(decode_temperature(payload).unwrap(), temp);
+            }
+        }
+
+        #[test]
+        fn encode_rounds() {
+            let payload =
+                encode_temperature(ThermodynamicTemperature::new::<degree_celsius>(25.03)).unwrap();
+            assert_eq!(payload, TemperaturePayload::from([0x01, 0x90]));
         }
 
         mod raw {
@@ -113,46 +521,383 @@ mod tests {
                 TemperaturePayload,
             };
 
-            fn assert_temp_eq(payload: [u8; 2], raw_temp: Temperature) {
-                assert_eq!(
-                    raw_temp,
-                    raw::decode_temperature(TemperaturePayload::from(payload)).unwrap()
-                );
+            /// Datasheet § 5.1.3 register values and the temperatures they stand for.
+            const CONFORMANCE: [([u8; 2], f64); 16] = [
+                ([0x07, 0xD0], 125.0),
+                ([0x01, 0x94], 25.25),
+                ([0x01, 0x91], 25.0625),
+                ([0x00, 0x0A], 0.625),
+                ([0x00, 0x08], 0.5),
+                ([0x00, 0x02], 0.125),
+                ([0x00, 0x01], 0.0625),
+                ([0x00, 0x00], 0.0),
+                ([0x1F, 0xFF], -0.0625),
+                ([0x1F, 0xFE], -0.125),
+                ([0x1F, 0xF8], -0.5),
+                ([0x1F, 0xF6], -0.625),
+                ([0x1F, 0xF0], -1.0),
+                ([0x1E, 0x6F], -25.0625),
+                ([0x1E, 0x6C], -25.25),
+                ([0x1D, 0x80], -40.0),
+            ];
+
+            #[test]
+            fn conformance() {
+                for (payload, temp) in CONFORMANCE {
+                    assert_eq!(
+                        raw::decode_temperature(TemperaturePayload::from(payload)).unwrap(),
+                        Temperature::from_num(temp),
+                        "decoding {payload:02X?}"
+                    );
+                    assert_eq!(
+                        raw::encode_temperature(Temperature::from_num(temp)),
+                        TemperaturePayload::from(payload),
+                        "encoding {temp}°C"
+                    );
+                }
+            }
+
+            #[test]
+            fn alert_flags_are_ignored() {
+                for (payload, temp) in CONFORMANCE {
+                    let flagged = [payload[0] | 0b1110_0000, payload[1]];
+                    assert_eq!(
+                        raw::decode_temperature(TemperaturePayload::from(flagged)).unwrap(),
+                        Temperature::from_num(temp)
+                    );
+                }
+            }
+
+            #[test]
+            fn round_trip() {
+                for bits in 0..=0x1FFF_u16 {
+                    let payload = TemperaturePayload::from(bits.to_be_bytes());
+                    let temp = raw::decode_temperature(payload).unwrap();
+                    assert_eq!(raw::encode_temperature(temp), payload);
+                }
+            }
+        }
+    }
+
+    mod limit {
+        use super::*;
+        use crate::{
+            decode::mcp9808::{decode_limit, encode_limit, LimitPayload},
+            test_util::temp,
+        };
+
+        #[test]
+        fn round_trip() {
+            for quarters in -40 * 4..=125 * 4 {
+                let temp = temp(f64::from(quarters) / 4.0);
+                let payload = encode_limit(temp).unwrap();
+                assert_eq!(decode_limit(payload).unwrap(), temp);
+            }
+        }
+
+        #[test]
+        fn out_of_range() {
+            assert_eq!(
+                encode_limit(temp(125.25)),
+                Err(Error::InvalidTemperature(125.25))
+            );
+            assert_eq!(
+                encode_limit(temp(-40.25)),
+                Err(Error::InvalidTemperature(-40.25))
+            );
+            // +127°C.
+            assert!(decode_limit(LimitPayload::from([0x07, 0xF0])).is_err());
+        }
+
+        #[test]
+        fn unrepresentable() {
+            assert!(matches!(
+                encode_limit(temp(25.1)),
+                Err(Error::InvalidLimit(..))
+            ));
+            assert!(matches!(
+                encode_limit(temp(-0.125)),
+                Err(Error::InvalidLimit(..))
+            ));
+        }
+
+        mod raw {
+            use crate::decode::mcp9808::{
+                raw::{self, Limit},
+                LimitPayload,
+            };
+
+            /// Register values and the limits they stand for.
+            const CONFORMANCE: [([u8; 2], f64); 8] = [
+                ([0x07, 0xD0], 125.0),
+                ([0x05, 0x5C], 85.75),
+                ([0x01, 0x94], 25.25),
+                ([0x00, 0x04], 0.25),
+                ([0x00, 0x00], 0.0),
+                ([0x1F, 0xFC], -0.25),
+                ([0x1E, 0x6C], -25.25),
+                ([0x1D, 0x80], -40.0),
+            ];
+
+            #[test]
+            fn conformance() {
+                for (payload, limit) in CONFORMANCE {
+                    assert_eq!(
+                        raw::decode_limit(LimitPayload::from(payload)).unwrap(),
+                        Limit::from_num(limit),
+                        "decoding {payload:02X?}"
+                    );
+                    assert_eq!(
+                        raw::encode_limit(Limit::from_num(limit)),
+                        LimitPayload::from(payload),
+                        "encoding {limit}°C"
+                    );
+                }
             }
 
             #[test]
-            fn zero_celsius() {
-                assert_temp_eq([0b0000_0000, 0b0000_0000], Temperature::from_num(0.0));
+            fn unimplemented_bits_are_ignored() {
+                for (payload, limit) in CONFORMANCE {
+                    let flagged = [payload[0] | 0b1110_0000, payload[1] | 0b0000_0011];
+                    assert_eq!(
+                        raw::decode_limit(LimitPayload::from(flagged)).unwrap(),
+                        Limit::from_num(limit)
+                    );
+                }
             }
 
             #[test]
-            fn slightly_above_zero_celsius() {
-                assert_temp_eq([0b0000_0000, 0b0000_0001], Temperature::from_num(0.062));
-                assert_temp_eq([0b0000_0000, 0b0000_0010], Temperature::from_num(0.125));
-                assert_temp_eq([0b0000_0000, 0b0000_1000], Temperature::from_num(0.500));
-                assert_temp_eq([0b0000_0000, 0b0000_1010], Temperature::from_num(0.625));
+            fn round_trip() {
+                for bits in (0..=0x1FFF_u16).map(|bits| bits & !0b11) {
+                    let payload = LimitPayload::from(bits.to_be_bytes());
+                    let limit = raw::decode_limit(payload).unwrap();
+                    assert_eq!(raw::encode_limit(limit), payload);
+                }
+            }
+        }
+    }
+
+    mod config {
+        use crate::decode::mcp9808::{decode_config, Config, ConfigPayload};
+
+        #[test]
+        fn power_on_default() {
+            assert_eq!(
+                decode_config(ConfigPayload::from([0x00, 0x00])).unwrap(),
+                Config::default()
+            );
+        }
+
+        mod raw {
+            use crate::decode::mcp9808::{
+                raw, AlertMode, AlertPolarity, AlertSelect, Config, ConfigPayload, Hysteresis,
+            };
+
+            /// Register values with a single field set, and the configuration they stand for.
+            fn single_fields() -> [([u8; 2], Config); 12] {
+                let config = Config::default();
+                [
+                    (
+                        [0x02, 0x00],
+                        Config {
+                            hysteresis: Hysteresis::OnePointFive,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x04, 0x00],
+                        Config {
+                            hysteresis: Hysteresis::Three,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x06, 0x00],
+                        Config {
+                            hysteresis: Hysteresis::Six,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x01, 0x00],
+                        Config {
+                            shutdown: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x80],
+                        Config {
+                            critical_lock: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x40],
+                        Config {
+                            window_lock: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x20],
+                        Config {
+                            interrupt_clear: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x10],
+                        Config {
+                            alert_asserted: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x08],
+                        Config {
+                            alert_enabled: true,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x04],
+                        Config {
+                            alert_select: AlertSelect::CriticalOnly,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x02],
+                        Config {
+                            alert_polarity: AlertPolarity::ActiveHigh,
+                            ..config
+                        },
+                    ),
+                    (
+                        [0x00, 0x01],
+                        Config {
+                            alert_mode: AlertMode::Interrupt,
+                            ..config
+                        },
+                    ),
+                ]
             }
 
             #[test]
-            fn slightly_below_zero_celsius() {
-                assert_temp_eq([0b0001_0000, 0b0000_0001], Temperature::from_num(-0.062));
-                assert_temp_eq([0b0001_0000, 0b0000_0010], Temperature::from_num(-0.125));
-                assert_temp_eq([0b0001_0000, 0b0000_1000], Temperature::from_num(-0.500));
-                assert_temp_eq([0b0001_0000, 0b0000_1010], Temperature::from_num(-0.625));
+            fn conformance() {
+                for (payload, config) in single_fields() {
+                    assert_eq!(
+                        raw::decode_config(ConfigPayload::from(payload)).unwrap(),
+                        config,
+                        "decoding {payload:02X?}"
+                    );
+                    assert_eq!(
+                        raw::encode_config(config),
+                        ConfigPayload::from(payload),
+                        "encoding {config:?}"
+                    );
+                }
             }
 
             #[test]
-            fn above_zero_celsius() {
-                assert_temp_eq([0b0000_0001, 0b1001_0100], Temperature::from_num(25.250));
+            fn unimplemented_bits_are_ignored() {
+                assert_eq!(
+                    raw::decode_config(ConfigPayload::from([0xF8, 0x00])).unwrap(),
+                    Config::default()
+                );
             }
 
             #[test]
-            fn below_zero_celsius() {
-                assert_temp_eq([0b0001_0001, 0b1001_0100], Temperature::from_num(-25.250));
+            fn round_trip() {
+                for bits in 0..=0x07FF_u16 {
+                    let payload = ConfigPayload::from(bits.to_be_bytes());
+                    let config = raw::decode_config(payload).unwrap();
+                    assert_eq!(raw::encode_config(config), payload);
+                }
             }
         }
     }
 
+    mod resolution {
+        use float_eq::assert_float_eq;
+        use uom::si::time::millisecond;
+
+        use crate::decode::mcp9808::{decode_resolution, Resolution, ResolutionPayload};
+
+        #[test]
+        fn power_on_default() {
+            assert_eq!(
+                decode_resolution(ResolutionPayload::from([0x03])).unwrap(),
+                Resolution::default()
+            );
+        }
+
+        #[test]
+        fn conversion_time() {
+            assert_float_eq!(
+                Resolution::Half.conversion_time().get::<millisecond>(),
+                30.0,
+                abs <= 1e-9
+            );
+            assert_float_eq!(
+                Resolution::Sixteenth.conversion_time().get::<millisecond>(),
+                250.0,
+                abs <= 1e-9
+            );
+        }
+
+        mod raw {
+            use crate::decode::mcp9808::{raw, Resolution, ResolutionPayload};
+
+            /// Register values and the resolutions they stand for.
+            const CONFORMANCE: [(u8, Resolution); 4] = [
+                (0x00, Resolution::Half),
+                (0x01, Resolution::Quarter),
+                (0x02, Resolution::Eighth),
+                (0x03, Resolution::Sixteenth),
+            ];
+
+            #[test]
+            fn conformance() {
+                for (payload, resolution) in CONFORMANCE {
+                    assert_eq!(
+                        raw::decode_resolution(ResolutionPayload::from([payload])).unwrap(),
+                        resolution,
+                        "decoding {payload:02X}"
+                    );
+                    assert_eq!(
+                        raw::encode_resolution(resolution),
+                        ResolutionPayload::from([payload]),
+                        "encoding {resolution:?}"
+                    );
+                }
+            }
+
+            #[test]
+            fn unimplemented_bits_are_ignored() {
+                for (payload, resolution) in CONFORMANCE {
+                    assert_eq!(
+                        raw::decode_resolution(ResolutionPayload::from([payload | 0xFC])).unwrap(),
+                        resolution
+                    );
+                }
+            }
+        }
+    }
+
+    mod address {
+        use crate::decode::mcp9808::{Address, Error};
+
+        #[test]
+        fn strappable_range() {
+            assert_eq!(Address::new(0x18), Ok(Address(0x18)));
+            assert_eq!(Address::new(0x1F), Ok(Address(0x1F)));
+            assert_eq!(Address::new(0x17), Err(Error::InvalidAddress(0x17)));
+            assert_eq!(Address::new(0x20), Err(Error::InvalidAddress(0x20)));
+        }
+    }
+
     mod manufacturer_id {
         mod raw {
             use crate::decode::mcp9808::{raw, DeviceId, DeviceIdPayload, Revision};
@@ -0,0 +1,186 @@
+use uom::si::{temperature_interval, thermodynamic_temperature::degree_celsius};
+
+use crate::{
+    decode::fan,
+    fan_curve::{self, FanCurve, Interpolation, MAX_CURVE_SIZE},
+    units::{TemperatureInterval, ThermodynamicTemperature},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a differential fan curve error.
+#[derive(Debug, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The measured temperature is unavailable.
+    #[error("missing temperature reading")]
+    MissingTemperature,
+    /// The ambient temperature is unavailable.
+    #[error("missing ambient temperature reading")]
+    MissingAmbient,
+    /// A fan curve error occurred.
+    #[error("fan curve error: {0}")]
+    FanCurveError(#[from] fan_curve::Error),
+}
+
+/// Returns the rise of `temp` over `ambient`.
+#[must_use]
+pub fn delta(
+    temp: ThermodynamicTemperature,
+    ambient: ThermodynamicTemperature,
+) -> TemperatureInterval {
+    TemperatureInterval::new::<temperature_interval::degree_celsius>(
+        temp.get::<degree_celsius>() - ambient.get::<degree_celsius>(),
+    )
+}
+
+/// Represents a differential fan curve point.
+pub type Point = (TemperatureInterval, fan::Speed);
+
+/// Represents a mapping from temperature rise over ambient to fan speed.
+///
+/// Useful when what matters is how much hotter the exhaust is than the intake, rather than how
+/// hot the exhaust is.
+#[derive(Debug, Clone)]
+pub struct DeltaCurve<const N: usize = MAX_CURVE_SIZE> {
+    // The curve is keyed on temperature rises in °C.
+    curve: FanCurve<N>,
+    fallback: Option<fan::Speed>,
+}
+
+impl<const N: usize> DeltaCurve<N> {
+    /// Creates a differential fan curve from `(temperature rise, fan speed)` points.
+    ///
+    /// Points must be sorted by strictly increasing temperature rise.
+    pub fn from_points<I>(points: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = Point>,
+    {
+        let curve = FanCurve::from_celsius_points(points.into_iter().map(|(delta, fan_speed)| {
+            (
+                delta.get::<temperature_interval::degree_celsius>(),
+                fan_speed,
+            )
+        }))?;
+
+        Ok(Self {
+            curve,
+            fallback: None,
+        })
+    }
+
+    /// Sets how the curve is sampled between its points.
+    #[must_use]
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.curve = self.curve.with_interpolation(interpolation);
+        self
+    }
+
+    /// Sets the fan speed used when either temperature is unavailable.
+    ///
+    /// Without a fallback, a missing temperature is an error.
+    #[must_use]
+    pub fn with_fallback(mut self, fan_speed: fan::Speed) -> Self {
+        self.fallback = Some(fan_speed);
+        self
+    }
+
+    /// Returns the fan speed for the rise of `temp` over `ambient`.
+    ///
+    /// If either temperature is missing, returns the fallback speed if there is one.
+    pub fn sample(
+        &self,
+        temp: Option<ThermodynamicTemperature>,
+        ambient: Option<ThermodynamicTemperature>,
+    ) -> Result<fan::Speed> {
+        match (temp, ambient, self.fallback) {
+            (Some(temp), Some(ambient), _) => Ok(self.curve.sample_celsius(
+                delta(temp, ambient).get::<temperature_interval::degree_celsius>(),
+            )?),
+            (_, _, Some(fallback)) => Ok(fallback),
+            (None, _, None) => Err(Error::MissingTemperature),
+            (_, None, None) => Err(Error::MissingAmbient),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+    use uom::si::ratio::percent;
+
+    use super::*;
+    use crate::test_util::{speed, temp};
+
+    fn new_curve() -> DeltaCurve<4> {
+        DeltaCurve::from_points([
+            (
+                TemperatureInterval::new::<temperature_interval::degree_celsius>(2.0),
+                speed(20.0),
+            ),
+            (
+                TemperatureInterval::new::<temperature_interval::degree_celsius>(10.0),
+                speed(100.0),
+            ),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn temperature_rise() {
+        let delta = delta(temp(35.0), temp(22.5));
+        assert_float_eq!(
+            delta.get::<temperature_interval::degree_celsius>(),
+            12.5,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn keyed_on_delta() {
+        let curve = new_curve();
+
+        let sample = |t, ambient| {
+            curve
+                .sample(Some(temp(t)), Some(temp(ambient)))
+                .unwrap()
+                .get::<percent>()
+        };
+        assert_float_eq!(sample(30.0, 30.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(36.0, 30.0), 60.0, abs <= 1e-9);
+        assert_float_eq!(sample(26.0, 20.0), 60.0, abs <= 1e-9);
+        assert_float_eq!(sample(50.0, 30.0), 100.0, abs <= 1e-9);
+        // Colder than ambient.
+        assert_float_eq!(sample(25.0, 30.0), 20.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn missing_readings() {
+        let curve = new_curve();
+
+        assert!(matches!(
+            curve.sample(None, Some(temp(20.0))),
+            Err(Error::MissingTemperature)
+        ));
+        assert!(matches!(
+            curve.sample(Some(temp(20.0)), None),
+            Err(Error::MissingAmbient)
+        ));
+    }
+
+    #[test]
+    fn fallback() {
+        let curve = new_curve().with_fallback(speed(100.0));
+
+        assert_eq!(curve.sample(None, Some(temp(20.0))).unwrap(), speed(100.0));
+        assert_eq!(curve.sample(Some(temp(20.0)), None).unwrap(), speed(100.0));
+        assert_eq!(curve.sample(None, None).unwrap(), speed(100.0));
+        assert_float_eq!(
+            curve
+                .sample(Some(temp(26.0)), Some(temp(20.0)))
+                .unwrap()
+                .get::<percent>(),
+            60.0,
+            abs <= 1e-9
+        );
+    }
+}
@@ -0,0 +1,430 @@
+use embedded_hal_async::delay::DelayNs;
+use heapless::Vec;
+use uom::si::{angular_velocity::revolution_per_minute, ratio::percent, time::millisecond};
+
+use crate::{
+    decode::{
+        self,
+        fan::{DriveMode, PeriodConfig, Speed, Tachometer},
+    },
+    units::{AngularVelocity, Time},
+};
+
+#[cfg(test)]
+pub mod sim;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a fan driver error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// A decode error occurred.
+    #[error("decode error: {0}")]
+    DecodeError(#[from] decode::fan::Error),
+}
+
+/// Represents a PWM output that drives a fan.
+pub trait Output {
+    /// Drives the fan at `duty`, switching at the frequency of `mode`.
+    fn set_duty(&mut self, duty: Speed, mode: DriveMode) -> decode::fan::Result<()>;
+}
+
+/// Represents a counter of tachometer pulses.
+pub trait PulseCounter {
+    /// Returns the number of pulses since the last reset, wrapping on overflow.
+    fn count(&mut self) -> u16;
+
+    /// Sets the count back to zero.
+    fn reset(&mut self);
+}
+
+/// Represents a monotonic clock.
+pub trait Clock {
+    /// Returns the time since an arbitrary fixed point, e.g. boot.
+    fn now(&self) -> Time;
+}
+
+/// Fans measured slower than this switch to timing pulses rather than counting them.
+const PERIOD_MODE_BELOW_RPM: f64 = 900.0;
+
+/// Fans measured faster than this switch back to counting pulses. Kept apart from
+/// [`PERIOD_MODE_BELOW_RPM`] so a fan near the boundary doesn't switch modes every measurement.
+const COUNT_MODE_ABOVE_RPM: f64 = 1100.0;
+
+/// Number of pulse periods timed per period-based measurement.
+const PERIOD_SAMPLES: usize = 4;
+
+/// How long pulses are counted for per count-based measurement, in milliseconds.
+const COUNT_WINDOW_MS: u32 = 500;
+
+/// How often the pulse counter is polled while timing pulses, in microseconds.
+///
+/// Edges are only timed to the poll interval, but the error doesn't accumulate across
+/// consecutive periods, so the averaged period is far more precise.
+const POLL_INTERVAL_US: u32 = 1000;
+
+/// How long a gated tachometer takes to produce valid pulses after the fan supply switches on, in
+/// milliseconds.
+const TACH_SETTLE_TIME_MS: u32 = 20;
+
+/// How long a gated tachometer's fan is held on to time a pulse, in milliseconds. Fans too slow
+/// to pulse twice in this time read as stalled.
+const STRETCH_TIMEOUT_MS: u32 = 150;
+
+/// Represents a fan driven by a PWM output and measured by a tachometer pulse counter.
+pub struct Fan<O, P, C> {
+    output: O,
+    counter: P,
+    clock: C,
+    drive_mode: DriveMode,
+    tachometer: Tachometer,
+    period_config: PeriodConfig,
+    duty: Option<Speed>,
+    /// Whether the fan is measured by timing pulses rather than counting them.
+    period_mode: bool,
+}
+
+impl<O: Output, P: PulseCounter, C: Clock + DelayNs> Fan<O, P, C> {
+    pub fn new(output: O, counter: P, clock: C) -> Self {
+        Self {
+            output,
+            counter,
+            clock,
+            drive_mode: DriveMode::default(),
+            tachometer: Tachometer::default(),
+            period_config: PeriodConfig::default(),
+            duty: None,
+            period_mode: false,
+        }
+    }
+
+    /// Sets how the fan is driven, e.g. low-frequency PWM for 3-pin fans.
+    ///
+    /// Takes effect on the next fan speed change.
+    #[must_use]
+    pub fn with_drive_mode(mut self, drive_mode: DriveMode) -> Self {
+        self.drive_mode = drive_mode;
+        self
+    }
+
+    /// Sets how the tachometer signal is decoded, e.g. for fans that don't pulse twice per
+    /// revolution.
+    #[must_use]
+    pub fn with_tachometer(mut self, tachometer: Tachometer) -> Self {
+        self.tachometer = tachometer;
+        self
+    }
+
+    /// Sets how slow fans are measured.
+    #[must_use]
+    pub fn with_period_config(mut self, period_config: PeriodConfig) -> Self {
+        self.period_config = period_config;
+        self
+    }
+
+    pub fn set_fan_speed(&mut self, speed: &Speed) -> Result<()> {
+        self.output.set_duty(*speed, self.drive_mode)?;
+        self.duty = Some(*speed);
+        Ok(())
+    }
+
+    /// Returns the current fan speed.
+    ///
+    /// Fast fans are measured by counting pulses over a fixed window. Slow fans, which give too
+    /// few pulses to count accurately, are measured by timing the pulses instead. The mode is
+    /// picked from previous measurements, with a deadband between the modes.
+    ///
+    /// Fans with a gated tachometer are briefly held on while a pulse is timed.
+    pub async fn rpm(&mut self) -> Result<AngularVelocity> {
+        if self.drive_mode.is_tach_gated() {
+            return self.rpm_stretched().await;
+        }
+
+        let rpm = if self.period_mode {
+            self.rpm_from_periods::<PERIOD_SAMPLES>(self.period_config)
+                .await
+        } else {
+            self.rpm_from_count().await
+        };
+        match &rpm {
+            Ok(rpm) => {
+                let rpm = rpm.get::<revolution_per_minute>();
+                if rpm < PERIOD_MODE_BELOW_RPM {
+                    self.period_mode = true;
+                } else if rpm > COUNT_MODE_ABOVE_RPM {
+                    self.period_mode = false;
+                }
+            }
+            // Too few pulses to count means the fan is slow enough to time instead.
+            Err(Error::DecodeError(decode::fan::Error::NotEnoughSamples(_))) => {
+                self.period_mode = true;
+            }
+            Err(_) => {}
+        }
+        rpm
+    }
+
+    /// Returns the fan speed of a fan with a gated tachometer.
+    ///
+    /// The tachometer is unpowered during the off-phase, so the supply is held on for long enough
+    /// to time a single pulse, then the duty is restored, so the fan isn't held at full speed long
+    /// enough to speed up. A fan that's switched off isn't turned on and reads as stopped.
+    async fn rpm_stretched(&mut self) -> Result<AngularVelocity> {
+        let Some(duty) = self.duty.filter(|duty| duty.get::<percent>() > 0.0) else {
+            return Ok(AngularVelocity::new::<revolution_per_minute>(0.0));
+        };
+        let config = PeriodConfig {
+            timeout: Time::new::<millisecond>(f64::from(STRETCH_TIMEOUT_MS))
+                .min(self.period_config.timeout),
+            ..self.period_config
+        };
+
+        self.output.set_duty(Speed::full(), self.drive_mode)?;
+        self.clock.delay_ms(TACH_SETTLE_TIME_MS).await;
+
+        let rpm = self.rpm_from_periods::<1>(config).await;
+        self.output.set_duty(duty, self.drive_mode)?;
+        rpm
+    }
+
+    /// Returns the fan speed from the number of pulses over a fixed window.
+    async fn rpm_from_count(&mut self) -> Result<AngularVelocity> {
+        self.counter.reset();
+        self.clock.delay_ms(COUNT_WINDOW_MS).await;
+        let pulse_count = self.counter.count();
+
+        Ok(self.tachometer.rpm_from_count(
+            pulse_count,
+            Time::new::<millisecond>(f64::from(COUNT_WINDOW_MS)),
+        )?)
+    }
+
+    /// Returns the fan speed from the time between up to `N` consecutive pulses.
+    ///
+    /// Takes at most the stall timeout, timing as many periods as fit. A fan that doesn't pulse
+    /// at all within the timeout is stalled, and one too slow to complete a period within it
+    /// can't be measured.
+    async fn rpm_from_periods<const N: usize>(
+        &mut self,
+        config: PeriodConfig,
+    ) -> Result<AngularVelocity> {
+        let mut periods = Vec::<Time, N>::new();
+        self.counter.reset();
+        let mut count = 0;
+        let mut last_edge = None;
+        let start = self.clock.now();
+        loop {
+            let now = self.clock.now();
+            let pulse_count = self.counter.count();
+            if pulse_count != count {
+                if let Some(last_edge) = last_edge {
+                    // Edges that arrived between polls share the elapsed time.
+                    let edges = f64::from(pulse_count.wrapping_sub(count));
+                    periods.push((now - last_edge) / edges).ok();
+                }
+                count = pulse_count;
+                last_edge = Some(now);
+            }
+            if periods.is_full() {
+                break;
+            }
+            if now - start >= config.timeout {
+                if last_edge.is_none() {
+                    periods.push(now - start).ok();
+                }
+                break;
+            }
+            self.clock.delay_us(POLL_INTERVAL_US).await;
+        }
+
+        Ok(self.tachometer.rpm_from_periods(&periods, &config)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use float_eq::assert_float_eq;
+    use uom::si::frequency::hertz;
+
+    use super::{sim::SimulatedFan, *};
+    use crate::{
+        test_util::{rpm, speed},
+        units::Frequency,
+    };
+
+    fn new_fan(sim: &SimulatedFan) -> Fan<SimulatedFan, SimulatedFan, SimulatedFan> {
+        Fan::new(sim.clone(), sim.clone(), sim.clone())
+    }
+
+    #[test]
+    fn drives_output() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+
+        fan.set_fan_speed(&speed(40.0)).unwrap();
+        assert_eq!(sim.duty(), speed(40.0));
+        assert_float_eq!(sim.frequency().get::<hertz>(), 25_000.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn counts_fast_fans() {
+        let sim = SimulatedFan::new(rpm(3000.0));
+        let mut fan = new_fan(&sim);
+
+        fan.set_fan_speed(&speed(100.0)).unwrap();
+        let measured = block_on(fan.rpm()).unwrap();
+        assert_float_eq!(measured.get::<revolution_per_minute>(), 3000.0, abs <= 60.0);
+        // Counting takes the whole window, regardless of speed.
+        assert_float_eq!(sim.now().get::<millisecond>(), 500.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn times_slow_fans() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+
+        fan.set_fan_speed(&speed(25.0)).unwrap();
+        // The first measurement counts, then the speed picks timing.
+        block_on(fan.rpm()).unwrap();
+        let start = sim.now();
+        let measured = block_on(fan.rpm()).unwrap();
+        assert_float_eq!(measured.get::<revolution_per_minute>(), 500.0, r2nd <= 0.01);
+        // Timing a handful of 60ms periods is quicker than counting.
+        assert!((sim.now() - start).get::<millisecond>() < 500.0);
+    }
+
+    #[test]
+    fn mode_switch_deadband() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+        let measure = |fan: &mut Fan<_, _, _>| {
+            let start = sim.now();
+            block_on(fan.rpm()).unwrap();
+            (sim.now() - start).get::<millisecond>()
+        };
+
+        // Just below the old single threshold, the fan keeps being counted.
+        fan.set_fan_speed(&speed(48.0)).unwrap();
+        measure(&mut fan);
+        assert_float_eq!(measure(&mut fan), 500.0, abs <= 1e-6);
+
+        // Once slow enough to time, it keeps being timed just above the threshold...
+        fan.set_fan_speed(&speed(40.0)).unwrap();
+        measure(&mut fan);
+        fan.set_fan_speed(&speed(52.0)).unwrap();
+        measure(&mut fan);
+        assert!(measure(&mut fan) < 500.0);
+
+        // ...until it's clearly fast again.
+        fan.set_fan_speed(&speed(60.0)).unwrap();
+        measure(&mut fan);
+        assert_float_eq!(measure(&mut fan), 500.0, abs <= 1e-6);
+    }
+
+    #[test]
+    fn timing_is_capped_at_timeout() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+
+        // 240rpm is a 125ms period, so not every period fits in the 500ms timeout.
+        fan.set_fan_speed(&speed(12.0)).unwrap();
+        block_on(fan.rpm()).unwrap();
+        let start = sim.now();
+        let measured = block_on(fan.rpm()).unwrap();
+        assert_float_eq!(measured.get::<revolution_per_minute>(), 240.0, r2nd <= 0.02);
+        assert!((sim.now() - start).get::<millisecond>() <= 501.0);
+    }
+
+    #[test]
+    fn single_pulse_switches_to_timing() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+
+        // 90rpm gives a single pulse per count window, too few to count.
+        fan.set_fan_speed(&speed(4.5)).unwrap();
+        assert!(block_on(fan.rpm()).is_err());
+        let measured = block_on(fan.rpm()).unwrap();
+        assert_float_eq!(measured.get::<revolution_per_minute>(), 90.0, r2nd <= 0.02);
+    }
+
+    #[test]
+    fn stopped_fan() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mut fan = new_fan(&sim);
+
+        fan.set_fan_speed(&speed(0.0)).unwrap();
+        assert_eq!(block_on(fan.rpm()).unwrap(), rpm(0.0));
+        // Times out rather than waiting for a pulse.
+        assert_eq!(block_on(fan.rpm()).unwrap(), rpm(0.0));
+    }
+
+    #[test]
+    fn stretches_gated_tachometer() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mode = DriveMode::low_frequency(Frequency::new::<hertz>(30.0)).unwrap();
+        let mut fan = new_fan(&sim).with_drive_mode(mode);
+
+        fan.set_fan_speed(&speed(50.0)).unwrap();
+        assert_float_eq!(sim.frequency().get::<hertz>(), 30.0, abs <= 1e-9);
+        let measured = block_on(fan.rpm()).unwrap();
+        assert!(measured > rpm(0.0));
+        // Held fully on while measuring, then restored.
+        assert_eq!(sim.max_duty(), speed(100.0));
+        assert_eq!(sim.duty(), speed(50.0));
+    }
+
+    #[test]
+    fn gated_tachometer_is_stretched_briefly() {
+        let mode = DriveMode::low_frequency(Frequency::new::<hertz>(30.0)).unwrap();
+        // The longest a reading may hold the fan on: settling, the timeout, and a final poll.
+        let bound = f64::from(TACH_SETTLE_TIME_MS + STRETCH_TIMEOUT_MS) + 1.0;
+
+        // 600rpm at full duty is a 50ms period, so a single period is timed well within the
+        // timeout.
+        let sim = SimulatedFan::new(rpm(600.0));
+        let mut fan = new_fan(&sim).with_drive_mode(mode);
+        fan.set_fan_speed(&speed(30.0)).unwrap();
+        for readings in 1..=10 {
+            let start = sim.now();
+            assert_float_eq!(
+                block_on(fan.rpm()).unwrap().get::<revolution_per_minute>(),
+                600.0,
+                r2nd <= 0.05
+            );
+            assert!((sim.now() - start).get::<millisecond>() <= bound);
+            assert!(sim.full_duty_time().get::<millisecond>() <= bound * f64::from(readings));
+        }
+        assert_eq!(sim.duty(), speed(30.0));
+
+        // 100rpm at full duty is a 300ms period, so the fan is only held on until the timeout.
+        let sim = SimulatedFan::new(rpm(100.0));
+        let mut fan = new_fan(&sim).with_drive_mode(mode);
+        fan.set_fan_speed(&speed(30.0)).unwrap();
+        assert_eq!(block_on(fan.rpm()).unwrap(), rpm(0.0));
+        assert!(sim.full_duty_time().get::<millisecond>() <= bound);
+    }
+
+    #[test]
+    fn gated_tachometer_of_stopped_fan() {
+        let sim = SimulatedFan::new(rpm(2000.0));
+        let mode = DriveMode::low_frequency(Frequency::new::<hertz>(30.0)).unwrap();
+        let mut fan = new_fan(&sim).with_drive_mode(mode);
+
+        fan.set_fan_speed(&speed(0.0)).unwrap();
+        assert_eq!(block_on(fan.rpm()).unwrap(), rpm(0.0));
+        // A stopped fan isn't switched on just to measure it.
+        assert_eq!(sim.max_duty(), speed(0.0));
+    }
+
+    #[test]
+    fn unreachable_frequency() {
+        let sim = SimulatedFan::new(rpm(2000.0)).with_clock(Frequency::new::<hertz>(1000.0));
+        let mut fan = new_fan(&sim);
+
+        assert!(matches!(
+            fan.set_fan_speed(&speed(50.0)),
+            Err(Error::DecodeError(decode::fan::Error::PwmError(..)))
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use std::{cell::RefCell, rc::Rc};
+
+use embedded_hal_async::delay::DelayNs;
+use uom::si::{
+    angular_velocity::revolution_per_second,
+    frequency::megahertz,
+    ratio::percent,
+    time::{nanosecond, second},
+};
+
+use super::{Clock, Output, PulseCounter};
+use crate::{
+    decode::{
+        self,
+        fan::{DriveMode, Speed, DEFAULT_PULSES_PER_REVOLUTION},
+    },
+    units::{AngularVelocity, Frequency, Time},
+};
+
+/// Represents the state shared by every handle to a simulated fan.
+#[derive(Debug)]
+struct State {
+    max_rpm: AngularVelocity,
+    clock: Frequency,
+    duty: Speed,
+    frequency: Frequency,
+    max_duty: Speed,
+    /// Time spent at full duty.
+    full_duty_time: Time,
+    now: Time,
+    /// Pulses since the start of the simulation.
+    pulses: f64,
+    /// Pulses at the last counter reset.
+    reset_at: f64,
+}
+
+/// Represents a simulated fan whose speed follows its duty instantly, with a tachometer that
+/// pulses twice per revolution.
+///
+/// Time only passes while delaying, so a handle can be used as the fan's output, pulse counter,
+/// and clock at once.
+#[derive(Debug, Clone)]
+pub struct SimulatedFan(Rc<RefCell<State>>);
+
+impl SimulatedFan {
+    /// Returns a stopped fan that spins at `max_rpm` at full duty.
+    #[must_use]
+    pub fn new(max_rpm: AngularVelocity) -> Self {
+        Self(Rc::new(RefCell::new(State {
+            max_rpm,
+            clock: Frequency::new::<megahertz>(125.0),
+            duty: Speed::default(),
+            frequency: Frequency::default(),
+            max_duty: Speed::default(),
+            full_duty_time: Time::default(),
+            now: Time::default(),
+            pulses: 0.0,
+            reset_at: 0.0,
+        })))
+    }
+
+    /// Sets the system clock the PWM output counts from.
+    #[must_use]
+    pub fn with_clock(self, clock: Frequency) -> Self {
+        self.0.borrow_mut().clock = clock;
+        self
+    }
+
+    /// Returns the duty the fan is driven at.
+    #[must_use]
+    pub fn duty(&self) -> Speed {
+        self.0.borrow().duty
+    }
+
+    /// Returns the highest duty the fan has been driven at.
+    #[must_use]
+    pub fn max_duty(&self) -> Speed {
+        self.0.borrow().max_duty
+    }
+
+    /// Returns how long the fan has been driven at full duty.
+    #[must_use]
+    pub fn full_duty_time(&self) -> Time {
+        self.0.borrow().full_duty_time
+    }
+
+    /// Returns the PWM frequency the fan is driven at.
+    #[must_use]
+    pub fn frequency(&self) -> Frequency {
+        self.0.borrow().frequency
+    }
+}
+
+impl Output for SimulatedFan {
+    fn set_duty(&mut self, duty: Speed, mode: DriveMode) -> decode::fan::Result<()> {
+        let mut state = self.0.borrow_mut();
+        duty.pwm_config(state.clock, mode)?;
+
+        state.duty = duty;
+        state.frequency = mode.frequency();
+        if duty > state.max_duty {
+            state.max_duty = duty;
+        }
+        Ok(())
+    }
+}
+
+impl PulseCounter for SimulatedFan {
+    fn count(&mut self) -> u16 {
+        let state = self.0.borrow();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let count = (state.pulses.floor() - state.reset_at.floor()) as u64 as u16;
+        count
+    }
+
+    fn reset(&mut self) {
+        let mut state = self.0.borrow_mut();
+        state.reset_at = state.pulses;
+    }
+}
+
+impl Clock for SimulatedFan {
+    fn now(&self) -> Time {
+        self.0.borrow().now
+    }
+}
+
+impl DelayNs for SimulatedFan {
+    async fn delay_ns(&mut self, ns: u32) {
+        let mut state = self.0.borrow_mut();
+        let elapsed = Time::new::<nanosecond>(f64::from(ns));
+        let revolutions =
+            state.max_rpm.get::<revolution_per_second>() * state.duty.get::<percent>() / 100.0
+                * elapsed.get::<second>();
+
+        state.now += elapsed;
+        if state.duty == Speed::full() {
+            state.full_duty_time += elapsed;
+        }
+        state.pulses += revolutions * f64::from(DEFAULT_PULSES_PER_REVOLUTION);
+    }
+}
@@ -1,6 +1,8 @@
+use core::cmp::Ordering;
+
 use enterpolation::{
     bspline::{BSpline, BSplineError},
-    Clamp, ConstSpace, Curve, Generator, Sorted,
+    Curve, Generator,
 };
 use heapless::Vec;
 use uom::si::{ratio::percent, thermodynamic_temperature::degree_celsius};
@@ -21,62 +23,284 @@ pub enum Error {
     /// A fan decode error occurred.
     #[error("fan decode error: {0}")]
     FanDecodeError(#[from] fan::Error),
-    #[error("heapless error")]
-    HeaplessError,
+    /// The curve has too few points.
+    #[error("too few curve points: expected x≥{MIN_CURVE_SIZE}, got {0} points")]
+    TooFewPoints(usize),
+    /// The curve has more points than its capacity.
+    #[error("too many curve points: expected x≤{0} points")]
+    TooManyPoints(usize),
+    /// A point is cooler than the point before it.
+    #[error("unsorted curve points: point {0} is cooler than the previous point")]
+    UnsortedPoints(usize),
+    /// Two points share the same temperature.
+    #[error("duplicate curve point: {0}°C appears more than once")]
+    DuplicatePoint(f64),
 }
 
-const MAX_CURVE_SIZE: usize = 2;
-type T = f64;
-type Knots = Sorted<[T; MAX_CURVE_SIZE]>;
-type Elements = [T; MAX_CURVE_SIZE];
-type Space = ConstSpace<T, MAX_CURVE_SIZE>;
+/// Default fan curve capacity.
+pub const MAX_CURVE_SIZE: usize = 8;
+/// Minimum number of points required to build a fan curve.
+pub const MIN_CURVE_SIZE: usize = 2;
+/// Linear B-spline workspace size, i.e. degree + 1.
+const LINEAR_SPACE: usize = 2;
+/// Quadratic B-spline workspace size, i.e. degree + 1.
+const QUADRATIC_SPACE: usize = 3;
+
+/// Represents a fan curve point.
+pub type Point = (ThermodynamicTemperature, fan::Speed);
 
-pub struct FanCurve(Clamp<BSpline<Knots, Elements, Space>>);
+/// Represents how a fan curve is sampled between its points.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Interpolation {
+    /// Holds the speed of the closest point at or below the temperature.
+    Step,
+    /// Interpolates linearly between neighbouring points.
+    #[default]
+    Linear,
+    /// Interpolates with a monotone cubic Hermite spline, which never overshoots neighbouring
+    /// points.
+    ///
+    /// See: Fritsch & Butland, "A method for constructing local monotone piecewise cubic
+    /// interpolants", 1984.
+    MonotoneCubic,
+    /// Approximates with a clamped quadratic B-spline using the points as control points.
+    ///
+    /// The curve passes through the first and last points and rounds off the points in between.
+    /// Curves with only two points are linear.
+    BSpline,
+}
+
+/// Represents a mapping from temperature to fan speed.
+///
+/// The curve holds up to `N` points with strictly increasing temperatures.
+/// Temperatures outside the curve are clamped to the first and last points.
+#[derive(Debug, Clone)]
+pub struct FanCurve<const N: usize = MAX_CURVE_SIZE> {
+    temps: Vec<f64, N>,
+    fan_speeds: Vec<f64, N>,
+    interpolation: Interpolation,
+}
 
-impl FanCurve {
+impl<const N: usize> FanCurve<N> {
+    /// Fails to compile for curves too small to hold the default curve.
+    const HOLDS_DEFAULT_CURVE: () = assert!(
+        N >= MIN_CURVE_SIZE,
+        "fan curves need room for at least MIN_CURVE_SIZE points"
+    );
+
+    /// Creates the default fan curve.
     pub fn new() -> Result<Self> {
-        let curve = Self::default_curve()?;
-        let (temps, fan_speeds) = Self::unzip_curve(curve);
-        let curve = BSpline::builder()
-            .elements(fan_speeds.into_array().map_err(|_| Error::HeaplessError)?)
-            .knots(temps.into_array().map_err(|_| Error::HeaplessError)?)
-            .constant::<MAX_CURVE_SIZE>()
-            .build()
-            .map_err(Error::SplineError)?
-            .clamp();
-        Ok(Self(curve))
+        #[allow(clippy::let_unit_value)]
+        let () = Self::HOLDS_DEFAULT_CURVE;
+        Self::from_points(Self::default_curve()?)
     }
 
-    fn default_curve() -> Result<Vec<(ThermodynamicTemperature, fan::Speed), MAX_CURVE_SIZE>> {
-        [(20.0, 30.0), (65.0, 100.0)]
-            .into_iter()
-            .map(|(temp, fan_speed)| {
-                Ok((
-                    ThermodynamicTemperature::new::<degree_celsius>(temp),
-                    fan::Speed::new(Ratio::new::<percent>(fan_speed))?,
-                ))
-            })
-            .collect::<Result<Vec<_, MAX_CURVE_SIZE>>>()
+    /// Creates a fan curve from `(temperature, fan speed)` points.
+    ///
+    /// Points must be sorted by strictly increasing temperature.
+    pub fn from_points<I>(points: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = Point>,
+    {
+        Self::from_celsius_points(
+            points
+                .into_iter()
+                .map(|(temp, fan_speed)| (temp.get::<degree_celsius>(), fan_speed)),
+        )
     }
 
-    fn unzip_curve<T>(collection: T) -> (Vec<f64, MAX_CURVE_SIZE>, Vec<f64, MAX_CURVE_SIZE>)
+    /// Creates a fan curve from `(°C, fan speed)` points, for curves keyed on something other
+    /// than an absolute temperature, e.g. a temperature rise.
+    pub(crate) fn from_celsius_points<I>(points: I) -> Result<Self>
     where
-        T: IntoIterator<Item = (ThermodynamicTemperature, fan::Speed)>,
+        I: IntoIterator<Item = (f64, fan::Speed)>,
     {
-        collection
-            .into_iter()
-            .map(|(temp, fan_speed)| (temp.get::<degree_celsius>(), fan_speed.get::<percent>()))
-            .unzip()
+        let mut temps = Vec::<f64, N>::new();
+        let mut fan_speeds = Vec::<f64, N>::new();
+
+        for (i, (temp, fan_speed)) in points.into_iter().enumerate() {
+            if let Some(prev) = temps.last() {
+                match temp.partial_cmp(prev) {
+                    Some(Ordering::Greater) => {}
+                    Some(Ordering::Equal) => return Err(Error::DuplicatePoint(temp)),
+                    _ => return Err(Error::UnsortedPoints(i)),
+                }
+            }
+
+            temps.push(temp).map_err(|_| Error::TooManyPoints(N))?;
+            fan_speeds
+                .push(fan_speed.get::<percent>())
+                .map_err(|_| Error::TooManyPoints(N))?;
+        }
+
+        if temps.len() < MIN_CURVE_SIZE {
+            return Err(Error::TooFewPoints(temps.len()));
+        }
+
+        Ok(Self {
+            temps,
+            fan_speeds,
+            interpolation: Interpolation::default(),
+        })
+    }
+
+    fn default_curve() -> Result<[Point; 2]> {
+        Ok([
+            (
+                ThermodynamicTemperature::new::<degree_celsius>(20.0),
+                fan::Speed::new(Ratio::new::<percent>(30.0))?,
+            ),
+            (
+                ThermodynamicTemperature::new::<degree_celsius>(65.0),
+                fan::Speed::new(Ratio::new::<percent>(100.0))?,
+            ),
+        ])
+    }
+
+    /// Returns the number of points in the curve.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.temps.len()
+    }
+
+    /// Returns `true` if the curve has no points, which is never the case.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.temps.is_empty()
+    }
+
+    /// Sets how the curve is sampled between its points.
+    #[must_use]
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Returns how the curve is sampled between its points.
+    #[must_use]
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
     }
 
     pub fn sample(&self, temp: ThermodynamicTemperature) -> Result<fan::Speed> {
+        self.sample_celsius(temp.get::<degree_celsius>())
+    }
+
+    /// Samples the curve at `temp` °C.
+    pub(crate) fn sample_celsius(&self, temp: f64) -> Result<fan::Speed> {
+        let fan_speed = match self.interpolation {
+            Interpolation::Step => self.sample_step(temp),
+            Interpolation::Linear => self.sample_linear(temp)?,
+            Interpolation::MonotoneCubic => self.sample_monotone_cubic(temp),
+            Interpolation::BSpline => self.sample_bspline(temp)?,
+        };
+
+        // Guard against rounding error pushing the curve just outside the valid range.
         Ok(fan::Speed::new(Ratio::new::<percent>(
-            self.0.gen(temp.get::<degree_celsius>()),
+            fan_speed.clamp(0.0, 100.0),
         ))?)
     }
+
+    /// Returns the index of the segment containing `temp`, i.e. `i` where `temps[i] ≤ temp`.
+    ///
+    /// Temperatures below the curve map to the first segment.
+    fn segment(&self, temp: f64) -> usize {
+        self.temps[1..self.temps.len() - 1]
+            .iter()
+            .take_while(|&&knot| knot <= temp)
+            .count()
+    }
+
+    fn sample_step(&self, temp: f64) -> f64 {
+        let index = self.temps.iter().take_while(|&&knot| knot <= temp).count();
+        self.fan_speeds[index.saturating_sub(1)]
+    }
+
+    fn sample_linear(&self, temp: f64) -> Result<f64> {
+        // A B-spline with as many knots as elements is degree 1, i.e. piecewise linear.
+        let curve = BSpline::builder()
+            .elements(self.fan_speeds.as_slice())
+            .knots(self.temps.as_slice())
+            .constant::<LINEAR_SPACE>()
+            .build()?
+            .clamp();
+        Ok(curve.gen(temp))
+    }
+
+    fn sample_monotone_cubic(&self, temp: f64) -> f64 {
+        let i = self.segment(temp);
+        let (t0, t1) = (self.temps[i], self.temps[i + 1]);
+        let (y0, y1) = (self.fan_speeds[i], self.fan_speeds[i + 1]);
+        let (m0, m1) = (self.tangent(i), self.tangent(i + 1));
+
+        let h = t1 - t0;
+        let s = ((temp - t0) / h).clamp(0.0, 1.0);
+        let (s2, s3) = (s * s, s * s * s);
+
+        // Cubic Hermite basis functions.
+        let h00 = 2.0 * s3 - 3.0 * s2 + 1.0;
+        let h10 = s3 - 2.0 * s2 + s;
+        let h01 = -2.0 * s3 + 3.0 * s2;
+        let h11 = s3 - s2;
+
+        let fan_speed = h00 * y0 + h10 * h * m0 + h01 * y1 + h11 * h * m1;
+        fan_speed.clamp(y0.min(y1), y0.max(y1))
+    }
+
+    /// Returns the slope between point `i` and point `i + 1`.
+    fn secant(&self, i: usize) -> f64 {
+        (self.fan_speeds[i + 1] - self.fan_speeds[i]) / (self.temps[i + 1] - self.temps[i])
+    }
+
+    /// Returns the Fritsch–Butland tangent at point `i`.
+    fn tangent(&self, i: usize) -> f64 {
+        let last = self.temps.len() - 1;
+
+        if i == 0 {
+            return self.secant(0);
+        }
+        if i == last {
+            return self.secant(last - 1);
+        }
+
+        let (d0, d1) = (self.secant(i - 1), self.secant(i));
+        if d0 * d1 <= 0.0 {
+            // Flatten local extrema so the curve can't overshoot.
+            return 0.0;
+        }
+
+        let h0 = self.temps[i] - self.temps[i - 1];
+        let h1 = self.temps[i + 1] - self.temps[i];
+        3.0 * (h0 + h1) / ((2.0 * h1 + h0) / d0 + (h1 + 2.0 * h0) / d1)
+    }
+
+    fn sample_bspline(&self, temp: f64) -> Result<f64> {
+        if self.temps.len() <= MIN_CURVE_SIZE {
+            return self.sample_linear(temp);
+        }
+
+        // A clamped quadratic B-spline needs one knot fewer than elements. Place the inner knots
+        // halfway between the inner points so each point's influence stays centred on it.
+        let last = self.temps.len() - 1;
+        let mut knots = Vec::<f64, N>::new();
+        knots.push(self.temps[0]).ok();
+        for i in 1..last - 1 {
+            knots.push((self.temps[i] + self.temps[i + 1]) / 2.0).ok();
+        }
+        knots.push(self.temps[last]).ok();
+
+        let curve = BSpline::builder()
+            .clamped()
+            .elements(self.fan_speeds.as_slice())
+            .knots(knots.as_slice())
+            .constant::<QUADRATIC_SPACE>()
+            .build()?
+            .clamp();
+        Ok(curve.gen(temp))
+    }
 }
 
-impl Default for FanCurve {
+impl<const N: usize> Default for FanCurve<N> {
     fn default() -> Self {
         Self::new().unwrap()
     }
@@ -98,9 +322,33 @@ mod tests {
 
     use super::*;
 
+    fn points(points: &[(f64, f64)]) -> Vec<Point> {
+        points
+            .iter()
+            .map(|&(temp, fan_speed)| {
+                (
+                    ThermodynamicTemperature::new::<degree_celsius>(temp),
+                    fan::Speed::new(Ratio::new::<percent>(fan_speed)).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn sample<const N: usize>(curve: &FanCurve<N>, temp: f64) -> f64 {
+        curve
+            .sample(ThermodynamicTemperature::new::<degree_celsius>(temp))
+            .unwrap()
+            .get::<percent>()
+    }
+
+    #[test]
+    fn default_curve_fits_smallest_capacity() {
+        assert_eq!(FanCurve::<MIN_CURVE_SIZE>::default().len(), 2);
+    }
+
     #[test]
     fn sample_curve() -> Result<()> {
-        let curve = FanCurve::new()?;
+        let curve = FanCurve::<2>::new()?;
 
         let actual_samples = (0..=100)
             .step_by(10)
@@ -127,4 +375,191 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn multi_point_curve() -> Result<()> {
+        let curve = FanCurve::<8>::from_points(points(&[
+            (20.0, 20.0),
+            (30.0, 25.0),
+            (40.0, 40.0),
+            (50.0, 60.0),
+            (60.0, 80.0),
+            (70.0, 100.0),
+        ]))?;
+
+        assert_eq!(curve.len(), 6);
+        assert_float_eq!(sample(&curve, 10.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 30.0), 25.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 35.0), 32.5, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 55.0), 70.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 80.0), 100.0, abs <= 1e-9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn too_few_points() {
+        assert!(matches!(
+            FanCurve::<8>::from_points(points(&[])),
+            Err(Error::TooFewPoints(0))
+        ));
+        assert!(matches!(
+            FanCurve::<8>::from_points(points(&[(20.0, 30.0)])),
+            Err(Error::TooFewPoints(1))
+        ));
+    }
+
+    #[test]
+    fn too_many_points() {
+        assert!(matches!(
+            FanCurve::<2>::from_points(points(&[(20.0, 30.0), (40.0, 50.0), (60.0, 70.0)])),
+            Err(Error::TooManyPoints(2))
+        ));
+    }
+
+    #[test]
+    fn unsorted_points() {
+        assert!(matches!(
+            FanCurve::<8>::from_points(points(&[(20.0, 30.0), (60.0, 70.0), (40.0, 50.0)])),
+            Err(Error::UnsortedPoints(2))
+        ));
+    }
+
+    #[test]
+    fn duplicate_points() {
+        assert!(matches!(
+            FanCurve::<8>::from_points(points(&[(20.0, 30.0), (20.0, 50.0)])),
+            Err(Error::DuplicatePoint(_))
+        ));
+    }
+
+    const MODES: [Interpolation; 4] = [
+        Interpolation::Step,
+        Interpolation::Linear,
+        Interpolation::MonotoneCubic,
+        Interpolation::BSpline,
+    ];
+
+    const MONOTONIC_POINTS: [(f64, f64); 6] = [
+        (20.0, 20.0),
+        (30.0, 22.0),
+        (35.0, 50.0),
+        (50.0, 55.0),
+        (60.0, 90.0),
+        (75.0, 100.0),
+    ];
+
+    fn monotonic_curve(interpolation: Interpolation) -> FanCurve<8> {
+        FanCurve::<8>::from_points(points(&MONOTONIC_POINTS))
+            .unwrap()
+            .with_interpolation(interpolation)
+    }
+
+    fn sweep() -> impl Iterator<Item = f64> {
+        (0..=1000).map(|x| f64::from(x) / 10.0)
+    }
+
+    #[test]
+    fn step_curve() {
+        let curve = monotonic_curve(Interpolation::Step);
+
+        assert_float_eq!(sample(&curve, 0.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 29.9), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 30.0), 22.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 49.9), 50.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 75.0), 100.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 100.0), 100.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn interpolating_modes_pass_through_points() {
+        for mode in [
+            Interpolation::Step,
+            Interpolation::Linear,
+            Interpolation::MonotoneCubic,
+        ] {
+            let curve = monotonic_curve(mode);
+            for (temp, fan_speed) in MONOTONIC_POINTS {
+                assert_float_eq!(sample(&curve, temp), fan_speed, abs <= 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn bspline_curve_is_clamped_to_end_points() {
+        let curve = monotonic_curve(Interpolation::BSpline);
+
+        assert_float_eq!(sample(&curve, 0.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 20.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 75.0), 100.0, abs <= 1e-9);
+        assert_float_eq!(sample(&curve, 100.0), 100.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn two_point_bspline_curve_is_linear() {
+        let linear = FanCurve::<2>::new().unwrap();
+        let bspline = FanCurve::<2>::new()
+            .unwrap()
+            .with_interpolation(Interpolation::BSpline);
+
+        for temp in sweep() {
+            assert_float_eq!(sample(&bspline, temp), sample(&linear, temp), abs <= 1e-9);
+        }
+    }
+
+    #[test]
+    fn monotonic_curves_are_monotonic() {
+        for mode in MODES {
+            let curve = monotonic_curve(mode);
+            let samples = sweep().map(|temp| sample(&curve, temp)).collect::<Vec<_>>();
+
+            for pair in samples.windows(2) {
+                assert!(pair[0] <= pair[1] + 1e-9, "{mode:?} is not monotonic");
+            }
+        }
+    }
+
+    #[test]
+    fn monotonic_curves_do_not_overshoot() {
+        for mode in [
+            Interpolation::Step,
+            Interpolation::Linear,
+            Interpolation::MonotoneCubic,
+        ] {
+            let curve = monotonic_curve(mode);
+
+            for pair in MONOTONIC_POINTS.windows(2) {
+                let ((t0, y0), (t1, y1)) = (pair[0], pair[1]);
+                for temp in sweep().filter(|temp| (t0..=t1).contains(temp)) {
+                    let fan_speed = sample(&curve, temp);
+                    assert!(
+                        (y0 - 1e-9..=y1 + 1e-9).contains(&fan_speed),
+                        "{mode:?} overshoots at {temp}°C: {fan_speed}%"
+                    );
+                }
+            }
+        }
+
+        // The B-spline rounds off points, but stays within the curve's overall range.
+        let curve = monotonic_curve(Interpolation::BSpline);
+        for temp in sweep() {
+            assert!((20.0 - 1e-9..=100.0 + 1e-9).contains(&sample(&curve, temp)));
+        }
+    }
+
+    #[test]
+    fn monotone_cubic_flattens_plateaus() {
+        let curve = FanCurve::<8>::from_points(points(&[
+            (20.0, 30.0),
+            (40.0, 60.0),
+            (50.0, 60.0),
+            (60.0, 100.0),
+        ]))
+        .unwrap()
+        .with_interpolation(Interpolation::MonotoneCubic);
+
+        for temp in sweep().filter(|temp| (40.0..=50.0).contains(temp)) {
+            assert_float_eq!(sample(&curve, temp), 60.0, abs <= 1e-9);
+        }
+    }
 }
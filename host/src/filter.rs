@@ -0,0 +1,339 @@
+use heapless::Deque;
+use num_traits::Float;
+use uom::si::{thermodynamic_temperature::degree_celsius, time::second};
+
+use crate::units::{ThermodynamicTemperature, Time};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a temperature filter error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The time constant isn't positive.
+    #[error("invalid time constant: expected x>0s, got {0}s")]
+    InvalidTimeConstant(f64),
+}
+
+/// Represents a filter over a stream of temperature readings.
+pub trait Filter {
+    /// Feeds a reading taken at `now` and returns the filtered temperature.
+    fn update(&mut self, now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature;
+
+    /// Forgets all previous readings.
+    fn reset(&mut self);
+
+    /// Feeds the output of this filter into `next`.
+    fn chain<F: Filter>(self, next: F) -> Chain<Self, F>
+    where
+        Self: Sized,
+    {
+        Chain(self, next)
+    }
+}
+
+/// Represents two filters applied one after the other.
+#[derive(Debug, Clone)]
+pub struct Chain<A, B>(A, B);
+
+impl<A: Filter, B: Filter> Filter for Chain<A, B> {
+    fn update(&mut self, now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature {
+        let temp = self.0.update(now, temp);
+        self.1.update(now, temp)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+/// Passes readings through unchanged.
+impl Filter for () {
+    fn update(&mut self, _now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature {
+        temp
+    }
+
+    fn reset(&mut self) {}
+}
+
+/// Represents an exponential moving average.
+///
+/// Readings are weighted by the time elapsed since the previous reading, so irregular sample
+/// intervals are handled correctly. After one time constant, the output has moved ~63% of the way
+/// to a new steady temperature.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    time_constant: f64,
+    state: Option<(Time, f64)>,
+}
+
+impl Ema {
+    pub fn new(time_constant: Time) -> Result<Self> {
+        let time_constant = time_constant.get::<second>();
+        if time_constant.is_nan() || time_constant <= 0.0 {
+            return Err(Error::InvalidTimeConstant(time_constant));
+        }
+
+        Ok(Self {
+            time_constant,
+            state: None,
+        })
+    }
+}
+
+impl Filter for Ema {
+    fn update(&mut self, now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature {
+        let temp = temp.get::<degree_celsius>();
+
+        let average = match self.state {
+            None => temp,
+            Some((last, average)) => {
+                let elapsed = (now - last).get::<second>().max(0.0);
+                let alpha = 1.0 - Float::exp(-elapsed / self.time_constant);
+                average + alpha * (temp - average)
+            }
+        };
+
+        self.state = Some((now, average));
+        ThermodynamicTemperature::new::<degree_celsius>(average)
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Represents a sliding median over the last `N` readings.
+///
+/// Rejects spikes lasting less than half the window.
+#[derive(Debug, Clone)]
+pub struct Median<const N: usize> {
+    window: Deque<f64, N>,
+}
+
+impl<const N: usize> Median<N> {
+    /// Fails to compile for empty windows.
+    const NOT_EMPTY: () = assert!(N > 0, "the window must hold at least one reading");
+
+    /// Creates an empty window. `N` must be at least 1:
+    ///
+    /// ```compile_fail
+    /// let filter = fan_controller::filter::Median::<0>::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NOT_EMPTY;
+        Self {
+            window: Deque::new(),
+        }
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for Median<N> {
+    fn update(&mut self, _now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature {
+        push(&mut self.window, temp.get::<degree_celsius>());
+
+        let mut sorted = [0.0; N];
+        let sorted = &mut sorted[..self.window.len()];
+        for (slot, &temp) in sorted.iter_mut().zip(self.window.iter()) {
+            *slot = temp;
+        }
+        sorted.sort_unstable_by(f64::total_cmp);
+
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+        ThermodynamicTemperature::new::<degree_celsius>(median)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// Represents a mean over the last `N` readings.
+#[derive(Debug, Clone)]
+pub struct MovingAverage<const N: usize> {
+    window: Deque<f64, N>,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Fails to compile for empty windows.
+    const NOT_EMPTY: () = assert!(N > 0, "the window must hold at least one reading");
+
+    /// Creates an empty window. `N` must be at least 1:
+    ///
+    /// ```compile_fail
+    /// let filter = fan_controller::filter::MovingAverage::<0>::new();
+    /// ```
+    #[must_use]
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NOT_EMPTY;
+        Self {
+            window: Deque::new(),
+        }
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for MovingAverage<N> {
+    fn update(&mut self, _now: Time, temp: ThermodynamicTemperature) -> ThermodynamicTemperature {
+        push(&mut self.window, temp.get::<degree_celsius>());
+
+        // Summing the window each time avoids accumulating rounding error in a running total.
+        #[allow(clippy::cast_precision_loss)]
+        let mean = self.window.iter().sum::<f64>() / self.window.len() as f64;
+        ThermodynamicTemperature::new::<degree_celsius>(mean)
+    }
+
+    fn reset(&mut self) {
+        self.window.clear();
+    }
+}
+
+/// Pushes `value` into `window`, evicting the oldest value if it's full.
+fn push<const N: usize>(window: &mut Deque<f64, N>, value: f64) {
+    if window.is_full() {
+        window.pop_front();
+    }
+    window.push_back(value).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use float_eq::assert_float_eq;
+
+    use super::*;
+
+    /// Feeds one reading per second through `filter`.
+    fn run<F: Filter>(filter: &mut F, temps: &[f64]) -> Vec<f64> {
+        temps
+            .iter()
+            .enumerate()
+            .map(|(i, &temp)| {
+                #[allow(clippy::cast_precision_loss)]
+                let now = Time::new::<second>(i as f64);
+                filter
+                    .update(now, ThermodynamicTemperature::new::<degree_celsius>(temp))
+                    .get::<degree_celsius>()
+            })
+            .collect()
+    }
+
+    fn assert_all_eq(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (&actual, &expected) in actual.iter().zip(expected) {
+            assert_float_eq!(actual, expected, abs <= 1e-9);
+        }
+    }
+
+    #[test]
+    fn invalid_time_constant() {
+        assert_eq!(
+            Ema::new(Time::new::<second>(0.0)).unwrap_err(),
+            Error::InvalidTimeConstant(0.0)
+        );
+    }
+
+    #[test]
+    fn ema_step_response() {
+        let mut ema = Ema::new(Time::new::<second>(5.0)).unwrap();
+        let mut temps = [40.0; 6];
+        temps[0] = 30.0;
+
+        let output = run(&mut ema, &temps);
+        assert_float_eq!(output[0], 30.0, abs <= 1e-9);
+        // After one time constant, ~63% of the step.
+        assert_float_eq!(
+            output[5],
+            30.0 + 10.0 * (1.0 - (-1.0f64).exp()),
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn ema_handles_irregular_intervals() {
+        let mut a = Ema::new(Time::new::<second>(5.0)).unwrap();
+        let mut b = Ema::new(Time::new::<second>(5.0)).unwrap();
+        let temp = ThermodynamicTemperature::new::<degree_celsius>;
+
+        a.update(Time::new::<second>(0.0), temp(30.0));
+        b.update(Time::new::<second>(0.0), temp(30.0));
+
+        // Two half-second steps at a constant temperature equal one second-long step.
+        a.update(Time::new::<second>(0.5), temp(40.0));
+        let a = a.update(Time::new::<second>(1.0), temp(40.0));
+        let b = b.update(Time::new::<second>(1.0), temp(40.0));
+        assert_float_eq!(
+            a.get::<degree_celsius>(),
+            b.get::<degree_celsius>(),
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn median_rejects_spikes() {
+        let mut median = Median::<3>::new();
+
+        let output = run(&mut median, &[30.0, 31.0, 90.0, 32.0, 33.0, -40.0, 34.0]);
+        assert_all_eq(&output, &[30.0, 30.5, 31.0, 32.0, 33.0, 32.0, 33.0]);
+    }
+
+    #[test]
+    fn moving_average() {
+        let mut mean = MovingAverage::<4>::new();
+
+        let output = run(&mut mean, &[30.0, 32.0, 34.0, 36.0, 38.0, 40.0]);
+        assert_all_eq(&output, &[30.0, 31.0, 32.0, 33.0, 35.0, 37.0]);
+    }
+
+    #[test]
+    fn single_reading_window() {
+        assert_all_eq(&run(&mut Median::<1>::new(), &[30.0, 90.0]), &[30.0, 90.0]);
+        assert_all_eq(
+            &run(&mut MovingAverage::<1>::new(), &[30.0, 90.0]),
+            &[30.0, 90.0],
+        );
+    }
+
+    #[test]
+    fn chain() {
+        let mut filter = Median::<3>::new().chain(MovingAverage::<2>::new());
+
+        let output = run(&mut filter, &[30.0, 30.0, 90.0, 30.0, 36.0, 36.0]);
+        assert_all_eq(&output, &[30.0, 30.0, 30.0, 30.0, 33.0, 36.0]);
+    }
+
+    #[test]
+    fn reset() {
+        let mut filter =
+            MovingAverage::<4>::new().chain(Ema::new(Time::new::<second>(5.0)).unwrap());
+
+        run(&mut filter, &[30.0, 32.0, 34.0]);
+        filter.reset();
+        assert_all_eq(&run(&mut filter, &[50.0]), &[50.0]);
+    }
+
+    #[test]
+    fn identity() {
+        assert_all_eq(&run(&mut (), &[30.0, 90.0]), &[30.0, 90.0]);
+    }
+}
@@ -0,0 +1,309 @@
+use uom::si::{angular_velocity::revolution_per_minute, ratio::percent, time::second};
+
+use crate::{
+    decode::fan,
+    units::{AngularVelocity, Ratio, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a fan health error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The maximum RPM isn't positive.
+    #[error("invalid max rpm: expected x>0rpm, got {0}rpm")]
+    InvalidMaxRpm(f64),
+    /// The degraded threshold is outside 0..=100%.
+    #[error("invalid degraded threshold: expected 0≤x≤100%, got {0}%")]
+    InvalidThreshold(f64),
+    /// The grace period is negative.
+    #[error("invalid grace period: expected x≥0s, got {0}s")]
+    InvalidGracePeriod(f64),
+    /// The fan is faulty.
+    #[error("fan fault: {0}")]
+    Fault(Status),
+}
+
+/// Represents fan health.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Status {
+    /// The fan spins as expected for its duty.
+    #[default]
+    Ok,
+    /// The fan stopped spinning while being driven.
+    Stalled,
+    /// The fan has never produced a tachometer signal while being driven, e.g. because it isn't
+    /// connected.
+    Missing,
+    /// The fan spins well below the expected speed for its duty.
+    Degraded,
+}
+
+impl core::fmt::Display for Status {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Stalled => "stalled",
+            Self::Missing => "missing",
+            Self::Degraded => "degraded",
+        })
+    }
+}
+
+/// Represents fault detection parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The fan's speed at full duty. The expected speed scales linearly with duty.
+    pub max_rpm: AngularVelocity,
+    /// The fan is degraded below this fraction of its expected speed.
+    pub degraded_below: Ratio,
+    /// Duties at or below this aren't expected to spin the fan, so they're never faulty.
+    pub min_duty: fan::Speed,
+    /// How long a fault must persist before it's reported, which gives the fan time to spin up or
+    /// down after a duty change.
+    pub grace_period: Time,
+}
+
+/// Represents a fault waiting out the grace period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Pending {
+    status: Status,
+    since: Time,
+}
+
+/// Represents a detector of fan faults from commanded duty and measured speed.
+#[derive(Debug, Clone)]
+pub struct FaultDetector {
+    config: Config,
+    status: Status,
+    pending: Option<Pending>,
+    seen_tach: bool,
+}
+
+impl FaultDetector {
+    pub fn new(config: Config) -> Result<Self> {
+        let max_rpm = config.max_rpm.get::<revolution_per_minute>();
+        if max_rpm.is_nan() || max_rpm <= 0.0 {
+            return Err(Error::InvalidMaxRpm(max_rpm));
+        }
+
+        let degraded_below = config.degraded_below.get::<percent>();
+        if !(0.0..=100.0).contains(&degraded_below) {
+            return Err(Error::InvalidThreshold(degraded_below));
+        }
+
+        let grace_period = config.grace_period.get::<second>();
+        if grace_period.is_nan() || grace_period < 0.0 {
+            return Err(Error::InvalidGracePeriod(grace_period));
+        }
+
+        Ok(Self {
+            config,
+            status: Status::Ok,
+            pending: None,
+            seen_tach: false,
+        })
+    }
+
+    /// Returns the reported fan health.
+    #[must_use]
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Returns an error if the fan is faulty.
+    pub fn check(&self) -> Result<()> {
+        match self.status {
+            Status::Ok => Ok(()),
+            status => Err(Error::Fault(status)),
+        }
+    }
+
+    /// Returns the fan speed expected at `duty`.
+    #[must_use]
+    pub fn expected_rpm(&self, duty: fan::Speed) -> AngularVelocity {
+        (self.config.max_rpm * *duty).into()
+    }
+
+    /// Feeds the fan speed `rpm` measured at `now` while the fan was driven at `duty`, and
+    /// returns the fan health.
+    ///
+    /// `rpm` is `None` if the tachometer couldn't be read, in which case the health is unchanged.
+    pub fn update(&mut self, now: Time, duty: fan::Speed, rpm: Option<AngularVelocity>) -> Status {
+        let Some(rpm) = rpm else {
+            return self.status;
+        };
+
+        let rpm = rpm.get::<revolution_per_minute>();
+        if rpm > 0.0 {
+            self.seen_tach = true;
+        }
+
+        let status = if duty <= self.config.min_duty {
+            Status::Ok
+        } else if rpm <= 0.0 {
+            if self.seen_tach {
+                Status::Stalled
+            } else {
+                Status::Missing
+            }
+        } else if rpm
+            < self.expected_rpm(duty).get::<revolution_per_minute>()
+                * self.config.degraded_below.get::<percent>()
+                / 100.0
+        {
+            Status::Degraded
+        } else {
+            Status::Ok
+        };
+
+        if status == Status::Ok {
+            self.status = Status::Ok;
+            self.pending = None;
+            return self.status;
+        }
+
+        match self.pending {
+            Some(pending) if pending.status == status => {
+                if now - pending.since >= self.config.grace_period {
+                    self.status = status;
+                }
+            }
+            _ => {
+                self.pending = Some(Pending { status, since: now });
+                if self.config.grace_period <= Time::default() {
+                    self.status = status;
+                }
+            }
+        }
+        self.status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::speed;
+
+    fn new_detector() -> FaultDetector {
+        FaultDetector::new(Config {
+            max_rpm: AngularVelocity::new::<revolution_per_minute>(2000.0),
+            degraded_below: Ratio::new::<percent>(50.0),
+            min_duty: speed(10.0),
+            grace_period: Time::new::<second>(5.0),
+        })
+        .unwrap()
+    }
+
+    fn update(detector: &mut FaultDetector, now: f64, duty: f64, rpm: f64) -> Status {
+        detector.update(
+            Time::new::<second>(now),
+            speed(duty),
+            Some(AngularVelocity::new::<revolution_per_minute>(rpm)),
+        )
+    }
+
+    #[test]
+    fn invalid_config() {
+        let config = Config {
+            max_rpm: AngularVelocity::new::<revolution_per_minute>(2000.0),
+            degraded_below: Ratio::new::<percent>(150.0),
+            min_duty: speed(10.0),
+            grace_period: Time::new::<second>(5.0),
+        };
+        assert_eq!(
+            FaultDetector::new(config).unwrap_err(),
+            Error::InvalidThreshold(150.0)
+        );
+        assert_eq!(
+            FaultDetector::new(Config {
+                max_rpm: AngularVelocity::new::<revolution_per_minute>(0.0),
+                ..config
+            })
+            .unwrap_err(),
+            Error::InvalidMaxRpm(0.0)
+        );
+        assert_eq!(
+            FaultDetector::new(Config {
+                degraded_below: Ratio::new::<percent>(50.0),
+                grace_period: Time::new::<second>(-1.0),
+                ..config
+            })
+            .unwrap_err(),
+            Error::InvalidGracePeriod(-1.0)
+        );
+    }
+
+    #[test]
+    fn ok() {
+        let mut detector = new_detector();
+
+        assert_eq!(update(&mut detector, 0.0, 50.0, 950.0), Status::Ok);
+        assert_eq!(update(&mut detector, 10.0, 50.0, 950.0), Status::Ok);
+        assert_eq!(detector.check(), Ok(()));
+    }
+
+    #[test]
+    fn missing() {
+        let mut detector = new_detector();
+
+        assert_eq!(update(&mut detector, 0.0, 50.0, 0.0), Status::Ok);
+        assert_eq!(update(&mut detector, 5.0, 50.0, 0.0), Status::Missing);
+        assert_eq!(detector.check(), Err(Error::Fault(Status::Missing)));
+    }
+
+    #[test]
+    fn stalled() {
+        let mut detector = new_detector();
+
+        update(&mut detector, 0.0, 50.0, 1000.0);
+        assert_eq!(update(&mut detector, 1.0, 50.0, 0.0), Status::Ok);
+        assert_eq!(update(&mut detector, 6.0, 50.0, 0.0), Status::Stalled);
+        // Recovers as soon as it spins again.
+        assert_eq!(update(&mut detector, 7.0, 50.0, 1000.0), Status::Ok);
+    }
+
+    #[test]
+    fn degraded() {
+        let mut detector = new_detector();
+
+        // 50% duty should spin at 1000rpm.
+        assert_eq!(update(&mut detector, 0.0, 50.0, 400.0), Status::Ok);
+        assert_eq!(update(&mut detector, 5.0, 50.0, 400.0), Status::Degraded);
+        assert_eq!(update(&mut detector, 6.0, 50.0, 600.0), Status::Ok);
+    }
+
+    #[test]
+    fn fault_must_persist() {
+        let mut detector = new_detector();
+
+        update(&mut detector, 0.0, 50.0, 1000.0);
+        update(&mut detector, 1.0, 50.0, 0.0);
+        // Spinning back up restarts the grace period.
+        update(&mut detector, 2.0, 50.0, 1000.0);
+        assert_eq!(update(&mut detector, 3.0, 50.0, 0.0), Status::Ok);
+        assert_eq!(update(&mut detector, 7.0, 50.0, 0.0), Status::Ok);
+        assert_eq!(update(&mut detector, 8.0, 50.0, 0.0), Status::Stalled);
+    }
+
+    #[test]
+    fn stopped_fan_is_not_faulty() {
+        let mut detector = new_detector();
+
+        update(&mut detector, 0.0, 50.0, 1000.0);
+        assert_eq!(update(&mut detector, 1.0, 0.0, 0.0), Status::Ok);
+        assert_eq!(update(&mut detector, 10.0, 5.0, 0.0), Status::Ok);
+    }
+
+    #[test]
+    fn unknown_rpm_holds_status() {
+        let mut detector = new_detector();
+
+        update(&mut detector, 0.0, 50.0, 0.0);
+        update(&mut detector, 5.0, 50.0, 0.0);
+        assert_eq!(
+            detector.update(Time::new::<second>(6.0), speed(50.0), None),
+            Status::Missing
+        );
+    }
+}
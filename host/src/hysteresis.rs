@@ -0,0 +1,207 @@
+use num_traits::Float;
+use uom::si::{ratio::percent, temperature_interval};
+
+use crate::{
+    decode::fan,
+    units::{Ratio, TemperatureInterval},
+};
+
+/// Represents hysteresis parameters.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// How far the temperature must rise above the last applied temperature before the fan speed
+    /// changes.
+    pub rising: TemperatureInterval,
+    /// How far the temperature must fall below the last applied temperature before the fan speed
+    /// changes.
+    pub falling: TemperatureInterval,
+    /// The smallest fan speed change worth applying.
+    pub min_speed_delta: Ratio,
+}
+
+impl Config {
+    /// Creates a symmetric deadband of `width`, centred on the last applied temperature.
+    #[must_use]
+    pub fn deadband(width: TemperatureInterval, min_speed_delta: Ratio) -> Self {
+        Self {
+            rising: width / 2.0,
+            falling: width / 2.0,
+            min_speed_delta,
+        }
+    }
+}
+
+/// Represents the last applied key and fan speed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct State {
+    /// The temperature, or temperature rise, in °C.
+    key: f64,
+    fan_speed: fan::Speed,
+}
+
+/// Represents a hysteresis filter between a fan curve and a fan.
+///
+/// Small temperature swings and small fan speed changes are ignored, so a temperature
+/// oscillating around a curve point doesn't make the fan hunt. Changes to fully stopped or fully
+/// on are always applied once the temperature has moved far enough.
+#[derive(Debug, Default, Clone)]
+pub struct Hysteresis {
+    config: Config,
+    state: Option<State>,
+}
+
+impl Hysteresis {
+    #[must_use]
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            state: None,
+        }
+    }
+
+    /// Returns the last applied fan speed, if any.
+    #[must_use]
+    pub fn fan_speed(&self) -> Option<fan::Speed> {
+        self.state.map(|state| state.fan_speed)
+    }
+
+    /// Forgets the last applied fan speed, so the next update is always applied.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Returns the fan speed to apply, or `None` if the fan should be left alone.
+    ///
+    /// `target` is the fan speed the curve or controller wants at `key`, the temperature or
+    /// temperature rise it's keyed on in °C.
+    pub fn update(&mut self, key: f64, target: fan::Speed) -> Option<fan::Speed> {
+        if let Some(state) = self.state {
+            let temp_delta = key - state.key;
+            let rising = self
+                .config
+                .rising
+                .get::<temperature_interval::degree_celsius>();
+            let falling = self
+                .config
+                .falling
+                .get::<temperature_interval::degree_celsius>();
+
+            if temp_delta < rising && -temp_delta < falling {
+                return None;
+            }
+
+            let target_percent = target.get::<percent>();
+            let speed_delta = Float::abs(target_percent - state.fan_speed.get::<percent>());
+            let is_limit = target_percent <= 0.0 || target_percent >= 100.0;
+
+            if speed_delta == 0.0
+                || (!is_limit && speed_delta < self.config.min_speed_delta.get::<percent>())
+            {
+                return None;
+            }
+        }
+
+        self.state = Some(State {
+            key,
+            fan_speed: target,
+        });
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::speed;
+
+    fn new_hysteresis() -> Hysteresis {
+        Hysteresis::new(Config {
+            rising: TemperatureInterval::new::<temperature_interval::degree_celsius>(1.0),
+            falling: TemperatureInterval::new::<temperature_interval::degree_celsius>(3.0),
+            min_speed_delta: Ratio::new::<percent>(5.0),
+        })
+    }
+
+    #[test]
+    fn first_update_is_applied() {
+        let mut hysteresis = new_hysteresis();
+        assert_eq!(hysteresis.update(30.0, speed(40.0)), Some(speed(40.0)));
+        assert_eq!(hysteresis.fan_speed(), Some(speed(40.0)));
+    }
+
+    #[test]
+    fn rising_threshold() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(40.0));
+
+        assert_eq!(hysteresis.update(30.9, speed(50.0)), None);
+        assert_eq!(hysteresis.update(31.0, speed(50.0)), Some(speed(50.0)));
+    }
+
+    #[test]
+    fn falling_threshold() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(40.0));
+
+        assert_eq!(hysteresis.update(28.0, speed(30.0)), None);
+        assert_eq!(hysteresis.update(27.1, speed(30.0)), None);
+        assert_eq!(hysteresis.update(27.0, speed(30.0)), Some(speed(30.0)));
+    }
+
+    #[test]
+    fn oscillation_does_not_hunt() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(40.0));
+
+        for i in 0..100 {
+            let (t, s) = if i % 2 == 0 {
+                (30.5, 45.0)
+            } else {
+                (29.5, 35.0)
+            };
+            assert_eq!(hysteresis.update(t, speed(s)), None);
+        }
+        assert_eq!(hysteresis.fan_speed(), Some(speed(40.0)));
+    }
+
+    #[test]
+    fn min_speed_delta() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(40.0));
+
+        assert_eq!(hysteresis.update(32.0, speed(44.0)), None);
+        assert_eq!(hysteresis.update(33.0, speed(45.0)), Some(speed(45.0)));
+    }
+
+    #[test]
+    fn limits_bypass_min_speed_delta() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(60.0, speed(98.0));
+        assert_eq!(hysteresis.update(62.0, speed(100.0)), Some(speed(100.0)));
+
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(2.0));
+        assert_eq!(hysteresis.update(25.0, speed(0.0)), Some(speed(0.0)));
+    }
+
+    #[test]
+    fn symmetric_deadband() {
+        let mut hysteresis = Hysteresis::new(Config::deadband(
+            TemperatureInterval::new::<temperature_interval::degree_celsius>(2.0),
+            Ratio::new::<percent>(0.0),
+        ));
+        hysteresis.update(30.0, speed(40.0));
+
+        assert_eq!(hysteresis.update(30.9, speed(41.0)), None);
+        assert_eq!(hysteresis.update(29.1, speed(39.0)), None);
+        assert_eq!(hysteresis.update(31.0, speed(42.0)), Some(speed(42.0)));
+    }
+
+    #[test]
+    fn reset() {
+        let mut hysteresis = new_hysteresis();
+        hysteresis.update(30.0, speed(40.0));
+        hysteresis.reset();
+        assert_eq!(hysteresis.update(30.0, speed(41.0)), Some(speed(41.0)));
+    }
+}
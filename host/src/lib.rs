@@ -1,3 +1,8 @@
+//! Fan control that's independent of the hardware.
+//!
+//! Anything time-dependent takes the current time as `now`, a monotonic timestamp, e.g. the time
+//! since boot.
+
 #![cfg_attr(not(test), no_std)]
 #![feature(error_in_core)]
 #![warn(clippy::suspicious, clippy::complexity, clippy::perf, clippy::pedantic)]
@@ -8,5 +13,21 @@
 )]
 
 pub use uom::si::f64 as units;
+pub mod aggregate;
+pub mod calibration;
+pub mod closed_loop;
+pub mod control;
 pub mod decode;
+pub mod differential;
+pub mod fan;
 pub mod fan_curve;
+pub mod filter;
+pub mod health;
+pub mod hysteresis;
+pub mod pid;
+pub mod pwm;
+pub mod ramp;
+pub mod sensor;
+#[cfg(test)]
+mod test_util;
+pub mod zero_rpm;
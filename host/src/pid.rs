@@ -0,0 +1,304 @@
+use uom::si::{ratio::percent, thermodynamic_temperature::degree_celsius, time::second};
+
+use crate::{
+    decode::fan,
+    units::{Ratio, ThermodynamicTemperature, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a PID controller error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The minimum fan speed is above the maximum fan speed.
+    #[error("invalid fan speed range: expected min≤max, got {0}%..{1}%")]
+    InvalidSpeedRange(f64, f64),
+    /// The sample period isn't positive.
+    #[error("invalid sample period: expected x>0s, got {0}s")]
+    InvalidSamplePeriod(f64),
+    /// A fan decode error occurred.
+    #[error("fan decode error: {0}")]
+    FanDecodeError(#[from] fan::Error),
+}
+
+/// Represents PID controller parameters.
+///
+/// The controller is reverse acting: temperatures above the setpoint speed the fan up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The temperature to hold.
+    pub setpoint: ThermodynamicTemperature,
+    /// Proportional gain, in % fan speed per °C of error.
+    pub kp: f64,
+    /// Integral gain, in % fan speed per °C of error per second.
+    pub ki: f64,
+    /// Derivative gain, in % fan speed per °C/s of temperature change.
+    pub kd: f64,
+    /// The expected time between updates.
+    ///
+    /// Used as the elapsed time for the first update, and as the most time an update will
+    /// integrate over, so a missed or late update doesn't kick the integral.
+    pub sample_period: Time,
+    /// The slowest fan speed the controller will output.
+    pub min_speed: fan::Speed,
+    /// The fastest fan speed the controller will output.
+    pub max_speed: fan::Speed,
+}
+
+/// Represents the last measured temperature and when it was measured.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct State {
+    time: Time,
+    temp: f64,
+}
+
+/// Represents a PID controller holding a temperature setpoint.
+///
+/// The derivative term acts on the measured temperature rather than the error, so changing the
+/// setpoint doesn't kick the fan. The integral term is limited to the maximum fan speed and stops
+/// accumulating while the output is saturated, so it doesn't wind up while the fan is pinned.
+#[derive(Debug, Clone)]
+pub struct Pid {
+    config: Config,
+    integral: f64,
+    state: Option<State>,
+}
+
+impl Pid {
+    pub fn new(config: Config) -> Result<Self> {
+        let (min, max) = (
+            config.min_speed.get::<percent>(),
+            config.max_speed.get::<percent>(),
+        );
+        if min > max {
+            return Err(Error::InvalidSpeedRange(min, max));
+        }
+
+        let sample_period = config.sample_period.get::<second>();
+        if sample_period <= 0.0 || !sample_period.is_finite() {
+            return Err(Error::InvalidSamplePeriod(sample_period));
+        }
+
+        Ok(Self {
+            config,
+            integral: 0.0,
+            state: None,
+        })
+    }
+
+    /// Returns the controller parameters.
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Changes the temperature to hold.
+    pub fn set_setpoint(&mut self, setpoint: ThermodynamicTemperature) {
+        self.config.setpoint = setpoint;
+    }
+
+    /// Clears the integral and derivative history.
+    pub fn reset(&mut self) {
+        self.integral = 0.0;
+        self.state = None;
+    }
+
+    /// Returns the fan speed for `temp` measured at `now`.
+    ///
+    /// The integral term is scaled by the time elapsed since the previous update, up to the
+    /// sample period, and the derivative term by the whole time elapsed, so updates needn't be
+    /// evenly spaced.
+    pub fn update(&mut self, now: Time, temp: ThermodynamicTemperature) -> Result<fan::Speed> {
+        let Config {
+            setpoint,
+            kp,
+            ki,
+            kd,
+            sample_period,
+            min_speed,
+            max_speed,
+        } = self.config;
+        let (min, max) = (min_speed.get::<percent>(), max_speed.get::<percent>());
+
+        let temp = temp.get::<degree_celsius>();
+        let error = temp - setpoint.get::<degree_celsius>();
+        let sample_period = sample_period.get::<second>();
+        let elapsed = self
+            .state
+            .map(|state| (now - state.time).get::<second>().max(0.0));
+        let dt = elapsed.map_or(sample_period, |elapsed| elapsed.min(sample_period));
+        let derivative = match (self.state, elapsed) {
+            (Some(state), Some(elapsed)) if elapsed > 0.0 => (temp - state.temp) / elapsed,
+            _ => 0.0,
+        };
+        self.state = Some(State { time: now, temp });
+
+        let proportional = kp * error;
+        let derivative = kd * derivative;
+        let integral = (self.integral + ki * error * dt).clamp(0.0, max);
+
+        let unclamped = proportional + integral + derivative;
+        let output = unclamped.clamp(min, max);
+
+        // Only integrate while the output isn't saturated, or when integrating pulls it back in.
+        let saturated_high = unclamped > max && integral > self.integral;
+        let saturated_low = unclamped < min && integral < self.integral;
+        if !saturated_high && !saturated_low {
+            self.integral = integral;
+        }
+
+        Ok(fan::Speed::new(Ratio::new::<percent>(output))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+    use crate::test_util::{speed, temp};
+
+    fn config(kp: f64, ki: f64, kd: f64) -> Config {
+        Config {
+            setpoint: temp(40.0),
+            kp,
+            ki,
+            kd,
+            sample_period: Time::new::<second>(1.0),
+            min_speed: speed(20.0),
+            max_speed: speed(100.0),
+        }
+    }
+
+    fn update(pid: &mut Pid, s: f64, t: f64) -> f64 {
+        pid.update(Time::new::<second>(s), temp(t))
+            .unwrap()
+            .get::<percent>()
+    }
+
+    #[test]
+    fn invalid_config() {
+        let mut invalid = config(1.0, 0.0, 0.0);
+        invalid.min_speed = speed(80.0);
+        invalid.max_speed = speed(20.0);
+        assert_eq!(
+            Pid::new(invalid).unwrap_err(),
+            Error::InvalidSpeedRange(80.0, 20.0)
+        );
+
+        let mut invalid = config(1.0, 0.0, 0.0);
+        invalid.sample_period = Time::new::<second>(0.0);
+        assert_eq!(
+            Pid::new(invalid).unwrap_err(),
+            Error::InvalidSamplePeriod(0.0)
+        );
+
+        let mut invalid = config(1.0, 0.0, 0.0);
+        invalid.sample_period = Time::new::<second>(-1.0);
+        assert_eq!(
+            Pid::new(invalid).unwrap_err(),
+            Error::InvalidSamplePeriod(-1.0)
+        );
+    }
+
+    #[test]
+    fn proportional() {
+        let mut pid = Pid::new(config(10.0, 0.0, 0.0)).unwrap();
+
+        assert_float_eq!(update(&mut pid, 0.0, 45.0), 50.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 1.0, 48.0), 80.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn output_is_clamped() {
+        let mut pid = Pid::new(config(10.0, 0.0, 0.0)).unwrap();
+
+        assert_float_eq!(update(&mut pid, 0.0, 30.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 1.0, 80.0), 100.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn integral_removes_steady_state_error() {
+        let mut pid = Pid::new(config(0.0, 2.0, 0.0)).unwrap();
+
+        // The first update integrates over one sample period.
+        assert_float_eq!(update(&mut pid, 0.0, 50.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 1.0, 50.0), 40.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 2.0, 50.0), 60.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 3.0, 40.0), 60.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn anti_windup() {
+        let mut pid = Pid::new(config(1.0, 5.0, 0.0)).unwrap();
+
+        // Hold the fan at full speed for a long time.
+        update(&mut pid, 0.0, 80.0);
+        for s in 1..1000 {
+            assert_float_eq!(update(&mut pid, f64::from(s), 80.0), 100.0, abs <= 1e-9);
+        }
+
+        // A wound-up integral would keep the fan pinned once the temperature is back at the
+        // setpoint.
+        assert_float_eq!(update(&mut pid, 1000.0, 40.0), 20.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn derivative_on_measurement() {
+        let mut pid = Pid::new(config(0.0, 0.0, 10.0)).unwrap();
+
+        assert_float_eq!(update(&mut pid, 0.0, 40.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 1.0, 45.0), 50.0, abs <= 1e-9);
+
+        // Moving the setpoint doesn't kick the output.
+        pid.set_setpoint(temp(20.0));
+        assert_float_eq!(update(&mut pid, 2.0, 45.0), 20.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn elapsed_time_scales_terms() {
+        let mut pid = Pid::new(config(0.0, 2.0, 10.0)).unwrap();
+
+        // Integral: 10°C × 2 × 1s = 20%.
+        assert_float_eq!(update(&mut pid, 0.0, 50.0), 20.0, abs <= 1e-9);
+        // Integral: 20% + 11°C × 2 × 0.5s = 31%, derivative: 1°C / 0.5s × 10 = 20%.
+        assert_float_eq!(update(&mut pid, 0.5, 51.0), 51.0, abs <= 1e-9);
+        // Integral: 31% + 11°C × 2 × 0.25s = 36.5%.
+        assert_float_eq!(update(&mut pid, 0.75, 51.0), 36.5, abs <= 1e-9);
+    }
+
+    #[test]
+    fn long_gap_is_clamped_to_sample_period() {
+        let mut pid = Pid::new(config(0.0, 2.0, 0.0)).unwrap();
+
+        assert_float_eq!(update(&mut pid, 0.0, 45.0), 20.0, abs <= 1e-9);
+        // A minute of missed updates only integrates over one sample period: 10% + 5°C × 2 × 1s.
+        assert_float_eq!(update(&mut pid, 60.0, 45.0), 20.0, abs <= 1e-9);
+        assert_float_eq!(update(&mut pid, 61.0, 45.0), 30.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn long_gap_does_not_kick_derivative() {
+        let mut config = config(0.0, 0.0, 10.0);
+        config.min_speed = speed(0.0);
+        let mut pid = Pid::new(config).unwrap();
+
+        assert_float_eq!(update(&mut pid, 0.0, 40.0), 0.0, abs <= 1e-9);
+        // 6°C over a minute is 0.1°C/s, not 6°C over one sample period.
+        assert_float_eq!(update(&mut pid, 60.0, 46.0), 1.0, abs <= 1e-9);
+    }
+
+    #[test]
+    fn holds_setpoint() {
+        let mut pid = Pid::new(config(4.0, 0.5, 1.0)).unwrap();
+
+        // A simple thermal model: constant heat input, cooling proportional to fan speed.
+        let mut t = 60.0;
+        for s in 0..600 {
+            let fan_speed = update(&mut pid, f64::from(s), t);
+            t += 0.5 - 0.01 * fan_speed * (t - 25.0) / 15.0;
+        }
+
+        assert_float_eq!(t, 40.0, abs <= 0.1);
+    }
+}
@@ -0,0 +1,249 @@
+use num_traits::Float;
+use uom::si::{frequency::hertz, ratio::percent};
+
+use crate::{
+    decode::fan,
+    units::{Frequency, Ratio},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents an RP2040 PWM solver error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The PWM frequency can't be produced from the system clock.
+    #[error("unreachable pwm frequency: expected {1}Hz≤x≤{2}Hz, got {0}Hz")]
+    UnreachableFrequency(f64, f64, f64),
+}
+
+/// Number of fractional bits in the RP2040 PWM clock divider.
+pub const DIVIDER_FRAC_BITS: u32 = 4;
+
+/// Smallest clock divider, i.e. the fastest PWM clock, in sixteenths.
+const MIN_DIVIDER: u16 = 1 << DIVIDER_FRAC_BITS;
+
+/// Largest clock divider, i.e. the slowest PWM clock, in sixteenths.
+const MAX_DIVIDER: u16 = (u8::MAX as u16) << DIVIDER_FRAC_BITS | 0xf;
+
+/// Longest PWM period, in counts.
+///
+/// One less than the counter range, so that a compare value of `period` (100% duty) still fits.
+const MAX_PERIOD: u32 = u16::MAX as u32;
+
+/// Represents an RP2040 PWM clock divider in 8.4 fixed point.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub struct Divider {
+    pub int: u8,
+    pub frac: u8,
+}
+
+impl Divider {
+    /// Returns the divider as raw 8.4 fixed point bits.
+    #[must_use]
+    pub fn to_bits(self) -> u16 {
+        u16::from(self.int) << DIVIDER_FRAC_BITS | u16::from(self.frac)
+    }
+
+    #[must_use]
+    pub fn value(self) -> f64 {
+        f64::from(self.to_bits()) / f64::from(MIN_DIVIDER)
+    }
+}
+
+/// Represents RP2040 PWM slice parameters for a frequency.
+#[derive(Debug, Copy, Clone, PartialEq, defmt::Format)]
+pub struct Solution {
+    pub divider: Divider,
+    pub top: u16,
+}
+
+impl Solution {
+    /// Finds the slice parameters closest to `frequency` when counting from `clock`.
+    ///
+    /// The RP2040 produces `clock / (divider × (top + 1))`, with an 8.4 fractional divider from 1
+    /// to 255 15/16 and a 16-bit `top`. The smallest divider that fits the period gives the finest
+    /// duty resolution, so among the dividers that keep at least half of that resolution, the one
+    /// landing closest to `frequency` is picked.
+    pub fn new(clock: Frequency, frequency: Frequency) -> Result<Self> {
+        let (clock, target) = (clock.get::<hertz>(), frequency.get::<hertz>());
+        let (min, max) = Self::range(clock);
+        if target.is_nan() || target < min || target > max {
+            return Err(Error::UnreachableFrequency(target, min, max));
+        }
+
+        // The number of sixteenths of a clock cycle in one PWM period.
+        let sixteenths = clock * f64::from(MIN_DIVIDER) / target;
+
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let smallest =
+            ((sixteenths / f64::from(MAX_PERIOD)).ceil() as u16).clamp(MIN_DIVIDER, MAX_DIVIDER);
+        let largest = smallest.saturating_mul(2).min(MAX_DIVIDER);
+
+        // Returns how far from `frequency` a divider lands, along with its parameters.
+        let candidate = |divider: u16| {
+            #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+            let period = ((sixteenths / f64::from(divider)).round() as u32).clamp(1, MAX_PERIOD);
+            let achieved =
+                clock * f64::from(MIN_DIVIDER) / (f64::from(divider) * f64::from(period));
+
+            #[allow(clippy::cast_possible_truncation)]
+            let solution = Self {
+                divider: Divider {
+                    int: (divider >> DIVIDER_FRAC_BITS) as u8,
+                    frac: (divider & 0xf) as u8,
+                },
+                top: (period - 1) as u16,
+            };
+            (Float::abs(achieved - target), solution)
+        };
+
+        let mut best = candidate(smallest);
+        for divider in smallest + 1..=largest {
+            if best.0 == 0.0 {
+                break;
+            }
+            let next = candidate(divider);
+            if next.0 < best.0 {
+                best = next;
+            }
+        }
+        Ok(best.1)
+    }
+
+    /// Returns the frequency these parameters produce when counting from `clock`.
+    #[must_use]
+    pub fn frequency(&self, clock: Frequency) -> Frequency {
+        clock / (self.divider.value() * f64::from(self.period()))
+    }
+
+    /// Returns the number of counts in one PWM period, i.e. the number of duty steps.
+    #[must_use]
+    pub fn period(&self) -> u32 {
+        u32::from(self.top) + 1
+    }
+
+    /// Returns the smallest possible change in duty.
+    #[must_use]
+    pub fn resolution(&self) -> Ratio {
+        Ratio::new::<percent>(100.0 / f64::from(self.period()))
+    }
+
+    /// Returns the compare value that produces `duty`.
+    #[must_use]
+    pub fn compare(&self, duty: fan::Speed) -> u16 {
+        #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+        let compare = (f64::from(self.period()) * duty.get::<percent>() / 100.0).round() as u16;
+        compare
+    }
+
+    /// Returns the slowest and fastest frequencies reachable from `clock`, in Hz.
+    fn range(clock: f64) -> (f64, f64) {
+        let slowest = f64::from(MAX_DIVIDER) / f64::from(MIN_DIVIDER) * f64::from(MAX_PERIOD);
+        (clock / slowest, clock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+    use uom::si::frequency::megahertz;
+
+    use super::*;
+    use crate::test_util::speed;
+
+    fn solve(clock_mhz: f64, frequency: f64) -> Result<Solution> {
+        Solution::new(
+            Frequency::new::<megahertz>(clock_mhz),
+            Frequency::new::<hertz>(frequency),
+        )
+    }
+
+    #[test]
+    fn fan_pwm_at_125mhz() {
+        let solution = solve(125.0, 25_000.0).unwrap();
+        assert_eq!(
+            solution,
+            Solution {
+                divider: Divider { int: 1, frac: 0 },
+                top: 4999,
+            }
+        );
+        assert_float_eq!(
+            solution
+                .frequency(Frequency::new::<megahertz>(125.0))
+                .get::<hertz>(),
+            25_000.0,
+            abs <= 1e-9
+        );
+        assert_float_eq!(solution.resolution().get::<percent>(), 0.02, abs <= 1e-9);
+    }
+
+    #[test]
+    fn low_frequency_uses_divider() {
+        // 125MHz / 30Hz is far more than 16 bits of counts.
+        let solution = solve(125.0, 30.0).unwrap();
+        assert!(solution.divider.to_bits() > MIN_DIVIDER);
+        assert_float_eq!(
+            solution
+                .frequency(Frequency::new::<megahertz>(125.0))
+                .get::<hertz>(),
+            30.0,
+            r2nd <= 1e-4
+        );
+        assert!(solution.period() > u32::from(u16::MAX) / 2);
+    }
+
+    #[test]
+    fn largest_divider_is_tried() {
+        // 4095 sixteenths × 40009 counts, with 40009 prime, is only exact at the largest divider.
+        let solution = solve(125.0, 125e6 * 16.0 / (4095.0 * 40009.0)).unwrap();
+        assert_eq!(
+            solution,
+            Solution {
+                divider: Divider { int: 255, frac: 15 },
+                top: 40008,
+            }
+        );
+    }
+
+    #[test]
+    fn fractional_divider_improves_accuracy() {
+        // 133MHz / 21kHz isn't a whole number of counts.
+        let clock = Frequency::new::<megahertz>(133.0);
+        let solution = Solution::new(clock, Frequency::new::<hertz>(21_000.0)).unwrap();
+        let achieved = solution.frequency(clock).get::<hertz>();
+        let whole_counts = 133e6 / (133e6_f64 / 21_000.0).round();
+        assert!((achieved - 21_000.0).abs() <= (whole_counts - 21_000.0).abs());
+    }
+
+    #[test]
+    fn unreachable_frequency() {
+        assert!(matches!(
+            solve(125.0, 1.0),
+            Err(Error::UnreachableFrequency(..))
+        ));
+        assert!(matches!(
+            solve(125.0, 200e6),
+            Err(Error::UnreachableFrequency(..))
+        ));
+        assert!(matches!(
+            solve(125.0, 0.0),
+            Err(Error::UnreachableFrequency(..))
+        ));
+    }
+
+    #[test]
+    fn compare() {
+        let solution = solve(125.0, 25_000.0).unwrap();
+        assert_eq!(solution.compare(speed(0.0)), 0);
+        assert_eq!(solution.compare(speed(50.0)), 2500);
+        assert_eq!(solution.compare(speed(100.0)), 5000);
+    }
+
+    #[test]
+    fn divider_bits() {
+        let divider = Divider { int: 3, frac: 4 };
+        assert_eq!(divider.to_bits(), 0x34);
+        assert_float_eq!(divider.value(), 3.25, abs <= 1e-9);
+    }
+}
@@ -0,0 +1,245 @@
+use uom::si::{ratio::percent, time::second};
+
+use crate::{
+    decode::fan,
+    units::{Ratio, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a ramp limiter error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// A rate limit isn't positive.
+    #[error("invalid ramp rate: expected x>0%/s, got {0}%/s")]
+    InvalidRate(f64),
+    /// A fan decode error occurred.
+    #[error("fan decode error: {0}")]
+    FanDecodeError(#[from] fan::Error),
+}
+
+/// Represents ramp limiter parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The fastest the fan may speed up, in % per second.
+    pub max_rise: f64,
+    /// The fastest the fan may slow down, in % per second.
+    pub max_fall: f64,
+}
+
+impl Default for Config {
+    /// Creates an unlimited ramp.
+    fn default() -> Self {
+        Self {
+            max_rise: f64::INFINITY,
+            max_fall: f64::INFINITY,
+        }
+    }
+}
+
+/// Represents the last output and when it was produced.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct State {
+    time: Time,
+    fan_speed: f64,
+}
+
+/// Represents a limit on how quickly the fan speed may change.
+///
+/// Sits between the curve or controller output and the fan, moving the fan towards its target
+/// speed by at most the configured rate for the time elapsed since the previous update.
+#[derive(Debug, Default, Clone)]
+pub struct Ramp {
+    config: Config,
+    state: Option<State>,
+}
+
+impl Ramp {
+    pub fn new(config: Config) -> Result<Self> {
+        for rate in [config.max_rise, config.max_fall] {
+            if rate.is_nan() || rate <= 0.0 {
+                return Err(Error::InvalidRate(rate));
+            }
+        }
+
+        Ok(Self {
+            config,
+            state: None,
+        })
+    }
+
+    /// Returns the last output fan speed, if any.
+    #[must_use]
+    pub fn fan_speed(&self) -> Option<fan::Speed> {
+        self.state
+            .and_then(|state| fan::Speed::new(Ratio::new::<percent>(state.fan_speed)).ok())
+    }
+
+    /// Forgets the last output fan speed, so the next update jumps straight to its target.
+    pub fn reset(&mut self) {
+        self.state = None;
+    }
+
+    /// Takes `fan_speed` as the last output at `now`, e.g. because the fan was overridden, so the
+    /// next update moves the fan on from it.
+    pub fn reset_to(&mut self, now: Time, fan_speed: fan::Speed) {
+        self.state = Some(State {
+            time: now,
+            fan_speed: fan_speed.get::<percent>(),
+        });
+    }
+
+    /// Returns the fan speed to apply at `now`, or `None` if it's unchanged.
+    pub fn update(&mut self, now: Time, target: fan::Speed) -> Result<Option<fan::Speed>> {
+        let target = target.get::<percent>();
+
+        let fan_speed = match self.state {
+            None => target,
+            Some(state) => {
+                let elapsed = (now - state.time).get::<second>().max(0.0);
+                let max_rise = Self::max_step(self.config.max_rise, elapsed);
+                let max_fall = Self::max_step(self.config.max_fall, elapsed);
+                let step = (target - state.fan_speed).min(max_rise).max(-max_fall);
+
+                if step == 0.0 {
+                    self.state = Some(State { time: now, ..state });
+                    return Ok(None);
+                }
+
+                state.fan_speed + step
+            }
+        };
+
+        self.state = Some(State {
+            time: now,
+            fan_speed,
+        });
+        Ok(Some(fan::Speed::new(Ratio::new::<percent>(fan_speed))?))
+    }
+
+    fn max_step(rate: f64, elapsed: f64) -> f64 {
+        if rate.is_infinite() {
+            f64::INFINITY
+        } else {
+            rate * elapsed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_eq::assert_float_eq;
+
+    use super::*;
+    use crate::test_util::speed;
+
+    fn update(ramp: &mut Ramp, now: f64, target: f64) -> Option<f64> {
+        ramp.update(Time::new::<second>(now), speed(target))
+            .unwrap()
+            .map(|fan_speed| fan_speed.get::<percent>())
+    }
+
+    fn new_ramp() -> Ramp {
+        Ramp::new(Config {
+            max_rise: 10.0,
+            max_fall: 5.0,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn invalid_rate() {
+        assert_eq!(
+            Ramp::new(Config {
+                max_rise: 0.0,
+                max_fall: 5.0,
+            })
+            .unwrap_err(),
+            Error::InvalidRate(0.0)
+        );
+        assert_eq!(
+            Ramp::new(Config {
+                max_rise: 10.0,
+                max_fall: -5.0,
+            })
+            .unwrap_err(),
+            Error::InvalidRate(-5.0)
+        );
+    }
+
+    #[test]
+    fn first_update_jumps_to_target() {
+        let mut ramp = new_ramp();
+        assert_eq!(update(&mut ramp, 0.0, 30.0), Some(30.0));
+    }
+
+    #[test]
+    fn rise_is_limited() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 0.0, 30.0);
+
+        assert_eq!(update(&mut ramp, 1.0, 100.0), Some(40.0));
+        assert_eq!(update(&mut ramp, 1.5, 100.0), Some(45.0));
+        assert_eq!(update(&mut ramp, 7.0, 100.0), Some(100.0));
+        assert_eq!(update(&mut ramp, 8.0, 100.0), None);
+    }
+
+    #[test]
+    fn fall_is_limited() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 0.0, 100.0);
+
+        assert_eq!(update(&mut ramp, 1.0, 30.0), Some(95.0));
+        assert_eq!(update(&mut ramp, 3.0, 30.0), Some(85.0));
+        assert_eq!(update(&mut ramp, 100.0, 30.0), Some(30.0));
+    }
+
+    #[test]
+    fn small_changes_are_not_delayed() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 0.0, 30.0);
+
+        assert_eq!(update(&mut ramp, 1.0, 35.0), Some(35.0));
+        assert_eq!(update(&mut ramp, 2.0, 32.0), Some(32.0));
+    }
+
+    #[test]
+    fn reversal() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 0.0, 30.0);
+
+        assert_eq!(update(&mut ramp, 2.0, 100.0), Some(50.0));
+        assert_eq!(update(&mut ramp, 3.0, 0.0), Some(45.0));
+    }
+
+    #[test]
+    fn reset_to_overridden_speed() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 0.0, 30.0);
+
+        ramp.reset_to(Time::new::<second>(1.0), speed(100.0));
+        assert_eq!(update(&mut ramp, 2.0, 30.0), Some(95.0));
+    }
+
+    #[test]
+    fn time_going_backwards_holds_speed() {
+        let mut ramp = new_ramp();
+        update(&mut ramp, 10.0, 30.0);
+
+        assert_eq!(update(&mut ramp, 5.0, 100.0), None);
+        assert_float_eq!(
+            ramp.fan_speed().unwrap().get::<percent>(),
+            30.0,
+            abs <= 1e-9
+        );
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let mut ramp = Ramp::default();
+        update(&mut ramp, 0.0, 0.0);
+
+        assert_eq!(update(&mut ramp, 0.0, 100.0), Some(100.0));
+        assert_eq!(update(&mut ramp, 0.0, 0.0), Some(0.0));
+    }
+}
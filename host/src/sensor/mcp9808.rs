@@ -0,0 +1,499 @@
+use embedded_hal_async::{
+    delay::DelayNs,
+    i2c::{self, I2c},
+};
+use heapless::Vec;
+use num_traits::Float;
+use uom::si::{thermodynamic_temperature::degree_celsius, time::microsecond};
+
+use crate::{
+    decode::{
+        self,
+        mcp9808::{
+            Address, Config, ConfigPayload, DeviceId, DeviceIdPayload, LimitPayload,
+            ManufacturerId, ManufacturerIdPayload, Resolution, ResolutionPayload, Revision,
+            TemperaturePayload, TemperatureReading,
+        },
+    },
+    units::{ThermodynamicTemperature, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a MCP9808 driver error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// A decode error occurred.
+    #[error("decode error: {0}")]
+    DecodeError(#[from] decode::mcp9808::Error),
+    /// An I2C transaction failed.
+    #[error("i2c error: {0}")]
+    I2CError(i2c::ErrorKind),
+    /// A limit didn't take, e.g. because the sensor's limits are locked.
+    #[error("limit not set: expected {0}°C, read back {1}°C")]
+    LimitNotSet(f64, f64),
+}
+
+/// Standard MCP9808 manufacturer ID.
+pub const MANUFACTURER_ID: ManufacturerId = ManufacturerId(0x54);
+/// Standard MCP9808 device ID.
+pub const DEVICE_ID: DeviceId = DeviceId(0x04);
+/// Default MCP9808 I2C address.
+pub const DEFAULT_ADDRESS: Address = match Address::new(0x18) {
+    Ok(address) => address,
+    Err(_) => panic!("the default address is strappable"),
+};
+/// Most MCP9808s that can share a bus, one per address.
+pub const MAX_SENSORS: usize = 8;
+
+/// Represents a MCP9808 register.
+///
+/// See: datasheet § 5.1, page 16.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+enum Register {
+    Config = 0x01,
+    UpperLimit = 0x02,
+    LowerLimit = 0x03,
+    CriticalLimit = 0x04,
+    Temperature = 0x05,
+    ManufacturerId = 0x06,
+    DeviceId = 0x07,
+    Resolution = 0x08,
+}
+
+/// Represents a MCP9808 alert limit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, defmt::Format)]
+pub enum Limit {
+    /// `T_UPPER`, the top of the alert window.
+    Upper,
+    /// `T_LOWER`, the bottom of the alert window.
+    Lower,
+    /// `T_CRIT`, the critical temperature.
+    Critical,
+}
+
+impl Limit {
+    fn register(self) -> Register {
+        match self {
+            Self::Upper => Register::UpperLimit,
+            Self::Lower => Register::LowerLimit,
+            Self::Critical => Register::CriticalLimit,
+        }
+    }
+}
+
+/// Represents a MCP9808 temperature sensor on any I2C bus.
+pub struct Mcp9808<I, D> {
+    i2c: I,
+    delay: D,
+    /// Address set by the sensor's A0–A2 pins.
+    address: Address,
+    resolution: Resolution,
+    /// `T_CRIT` as set and read back, or `None` while it's still the power-on default.
+    critical_limit: Option<ThermodynamicTemperature>,
+    /// When the temperature was last read, or the resolution last changed.
+    last_conversion: Option<Time>,
+}
+
+impl<I: I2c, D: DelayNs> Mcp9808<I, D> {
+    /// Returns the sensor at `address`.
+    ///
+    /// Readings are paced for the power-on resolution until `read_resolution` or
+    /// `set_resolution` is called, so call one first if the resolution may have been changed.
+    /// Sensors returned by [`discover`] already have theirs read.
+    pub fn new(i2c: I, delay: D, address: Address) -> Self {
+        Self {
+            i2c,
+            delay,
+            address,
+            resolution: Resolution::default(),
+            critical_limit: None,
+            last_conversion: None,
+        }
+    }
+
+    #[must_use]
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Returns the temperature at `now`.
+    pub async fn temp(&mut self, now: Time) -> Result<ThermodynamicTemperature> {
+        Ok(self.reading(now).await?.temp)
+    }
+
+    /// Returns the temperature at `now` along with the sensor's limit alert flags.
+    ///
+    /// The sensor powers up with every limit at 0°C, so `critical` is only reported once `T_CRIT`
+    /// has been set. Waits out the conversion time since the previous reading, so that each
+    /// reading is a fresh conversion.
+    pub async fn reading(&mut self, now: Time) -> Result<TemperatureReading> {
+        let mut payload = TemperaturePayload::ZERO;
+
+        let mut now = now;
+        if let Some(last_conversion) = self.last_conversion {
+            let ready = last_conversion + self.resolution.conversion_time();
+            if ready > now {
+                #[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+                self.delay
+                    .delay_us(Float::ceil((ready - now).get::<microsecond>()) as u32)
+                    .await;
+                now = ready;
+            }
+        }
+
+        self.read(Register::Temperature, payload.as_raw_mut_slice())
+            .await?;
+        self.last_conversion = Some(now);
+
+        let mut reading = decode::mcp9808::decode_temperature_reading(payload)?;
+        reading.critical &= self.critical_limit.is_some();
+        Ok(reading)
+    }
+
+    /// Returns the resolution last read or set.
+    #[must_use]
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Reads the resolution from the sensor and keeps it for pacing readings.
+    pub async fn read_resolution(&mut self) -> Result<Resolution> {
+        let mut payload = ResolutionPayload::ZERO;
+
+        self.read(Register::Resolution, payload.as_raw_mut_slice())
+            .await?;
+
+        self.resolution = decode::mcp9808::decode_resolution(payload)?;
+        Ok(self.resolution)
+    }
+
+    /// Sets the resolution at `now`. Finer resolutions take longer to convert.
+    ///
+    /// The next reading waits for a conversion at the new resolution.
+    pub async fn set_resolution(&mut self, resolution: Resolution, now: Time) -> Result<()> {
+        let payload = decode::mcp9808::encode_resolution(resolution);
+
+        self.write(Register::Resolution, payload.as_raw_slice())
+            .await?;
+
+        self.resolution = resolution;
+        self.last_conversion = Some(now);
+        Ok(())
+    }
+
+    pub async fn config(&mut self) -> Result<Config> {
+        let mut payload = ConfigPayload::ZERO;
+
+        self.read(Register::Config, payload.as_raw_mut_slice())
+            .await?;
+
+        Ok(decode::mcp9808::decode_config(payload)?)
+    }
+
+    pub async fn set_config(&mut self, config: Config) -> Result<()> {
+        let payload = decode::mcp9808::encode_config(config);
+
+        self.write(Register::Config, payload.as_raw_slice()).await
+    }
+
+    pub async fn limit(&mut self, limit: Limit) -> Result<ThermodynamicTemperature> {
+        let mut payload = LimitPayload::ZERO;
+
+        self.read(limit.register(), payload.as_raw_mut_slice())
+            .await?;
+
+        Ok(decode::mcp9808::decode_limit(payload)?)
+    }
+
+    /// Returns `T_CRIT` if it's been set, or `None` while it's still the power-on default.
+    #[must_use]
+    pub fn critical_limit(&self) -> Option<ThermodynamicTemperature> {
+        self.critical_limit
+    }
+
+    /// Sets an alert limit, which must be a multiple of 0.25°C.
+    ///
+    /// Writes are ignored by the sensor while the limit is locked, so `T_CRIT` is read back to
+    /// check it took before readings report it.
+    pub async fn set_limit(&mut self, limit: Limit, temp: ThermodynamicTemperature) -> Result<()> {
+        let payload = decode::mcp9808::encode_limit(temp)?;
+
+        self.write(limit.register(), payload.as_raw_slice()).await?;
+
+        if limit == Limit::Critical {
+            let actual = self.limit(limit).await?;
+            if actual != temp {
+                return Err(Error::LimitNotSet(
+                    temp.get::<degree_celsius>(),
+                    actual.get::<degree_celsius>(),
+                ));
+            }
+            self.critical_limit = Some(temp);
+        }
+        Ok(())
+    }
+
+    pub async fn manufacturer_id(&mut self) -> Result<ManufacturerId> {
+        let mut payload = ManufacturerIdPayload::ZERO;
+
+        self.read(Register::ManufacturerId, payload.as_raw_mut_slice())
+            .await?;
+
+        Ok(decode::mcp9808::decode_manufacturer_id(payload)?)
+    }
+
+    pub async fn device_id(&mut self) -> Result<(DeviceId, Revision)> {
+        let mut payload = DeviceIdPayload::ZERO;
+
+        self.read(Register::DeviceId, payload.as_raw_mut_slice())
+            .await?;
+
+        Ok(decode::mcp9808::decode_device_id(payload)?)
+    }
+
+    /// Returns whether the device at the sensor's address identifies as a MCP9808.
+    ///
+    /// An address that doesn't respond isn't a MCP9808.
+    pub async fn is_mcp9808(&mut self) -> bool {
+        matches!(self.manufacturer_id().await, Ok(MANUFACTURER_ID))
+            && matches!(self.device_id().await, Ok((DEVICE_ID, _)))
+    }
+
+    /// Returns the 7-bit bus address.
+    fn bus_address(&self) -> u8 {
+        #[allow(clippy::cast_possible_truncation)]
+        let address = *self.address as u8;
+        address
+    }
+
+    /// Reads `register` into `payload`.
+    async fn read(&mut self, register: Register, payload: &mut [u8]) -> Result<()> {
+        self.i2c
+            .write_read(self.bus_address(), &[register as u8], payload)
+            .await
+            .map_err(|e| Error::I2CError(i2c::Error::kind(&e)))
+    }
+
+    /// Writes `payload` to `register`.
+    async fn write(&mut self, register: Register, payload: &[u8]) -> Result<()> {
+        let mut buf = [0; 3];
+        buf[0] = register as u8;
+        buf[1..=payload.len()].copy_from_slice(payload);
+
+        self.i2c
+            .write(self.bus_address(), &buf[..=payload.len()])
+            .await
+            .map_err(|e| Error::I2CError(i2c::Error::kind(&e)))
+    }
+}
+
+/// Returns the MCP9808s found by probing every address a MCP9808 can be strapped to, with their
+/// resolutions read.
+///
+/// `on_bus` returns a sensor at the given address on the bus to scan.
+pub async fn discover<I: I2c, D: DelayNs>(
+    mut on_bus: impl FnMut(Address) -> Mcp9808<I, D>,
+) -> Vec<Mcp9808<I, D>, MAX_SENSORS> {
+    let mut sensors = Vec::new();
+    for address in decode::mcp9808::ADDRESS_RANGE.filter_map(|address| Address::new(address).ok()) {
+        let mut sensor = on_bus(address);
+        if sensor.is_mcp9808().await && sensor.read_resolution().await.is_ok() {
+            sensors.push(sensor).ok();
+        }
+    }
+    sensors
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use uom::si::time::millisecond;
+
+    use super::*;
+    use crate::{
+        sensor::mock::{Bus, Delay, Transaction},
+        test_util::temp,
+    };
+
+    const ADDRESS: u8 = 0x1A;
+
+    fn new_sensor(transactions: &[Transaction]) -> Mcp9808<Bus, Delay> {
+        Mcp9808::new(
+            Bus::new(transactions),
+            Delay::default(),
+            Address::new(u16::from(ADDRESS)).unwrap(),
+        )
+    }
+
+    fn ms(ms: f64) -> Time {
+        Time::new::<millisecond>(ms)
+    }
+
+    #[test]
+    fn reading() {
+        let mut sensor = new_sensor(&[Transaction::write_read(ADDRESS, &[0x05], &[0xC1, 0x94])]);
+
+        let reading = block_on(sensor.reading(ms(0.0))).unwrap();
+        assert_eq!(reading.temp, temp(25.25));
+        // T_CRIT is still the power-on 0°C, so the sensor's critical flag means nothing.
+        assert!(!reading.critical);
+        assert!(reading.above_upper);
+        assert!(!reading.below_lower);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn critical_reading() {
+        let mut sensor = new_sensor(&[
+            Transaction::write(ADDRESS, &[0x04, 0x01, 0x90]),
+            Transaction::write_read(ADDRESS, &[0x04], &[0x01, 0x90]),
+            Transaction::write_read(ADDRESS, &[0x05], &[0xC1, 0x94]),
+        ]);
+
+        block_on(sensor.set_limit(Limit::Critical, temp(25.0))).unwrap();
+        assert_eq!(sensor.critical_limit(), Some(temp(25.0)));
+        assert!(block_on(sensor.reading(ms(0.0))).unwrap().critical);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn locked_critical_limit() {
+        let mut sensor = new_sensor(&[
+            Transaction::write(ADDRESS, &[0x04, 0x05, 0x5C]),
+            Transaction::write_read(ADDRESS, &[0x04], &[0x00, 0x00]),
+        ]);
+
+        assert_eq!(
+            block_on(sensor.set_limit(Limit::Critical, temp(85.75))),
+            Err(Error::LimitNotSet(85.75, 0.0))
+        );
+        assert_eq!(sensor.critical_limit(), None);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn identity() {
+        let mut sensor = new_sensor(&[
+            Transaction::write_read(ADDRESS, &[0x06], &[0x00, 0x54]),
+            Transaction::write_read(ADDRESS, &[0x07], &[0x04, 0x00]),
+        ]);
+
+        assert_eq!(block_on(sensor.manufacturer_id()).unwrap(), MANUFACTURER_ID);
+        assert_eq!(
+            block_on(sensor.device_id()).unwrap(),
+            (DEVICE_ID, Revision(0x00))
+        );
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn config() {
+        let config = Config {
+            alert_enabled: true,
+            ..Config::default()
+        };
+        let mut sensor = new_sensor(&[
+            Transaction::write(ADDRESS, &[0x01, 0x00, 0x08]),
+            Transaction::write_read(ADDRESS, &[0x01], &[0x00, 0x08]),
+        ]);
+
+        block_on(sensor.set_config(config)).unwrap();
+        assert_eq!(block_on(sensor.config()).unwrap(), config);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn limits() {
+        let mut sensor = new_sensor(&[
+            Transaction::write(ADDRESS, &[0x04, 0x05, 0x5C]),
+            Transaction::write_read(ADDRESS, &[0x04], &[0x05, 0x5C]),
+            Transaction::write_read(ADDRESS, &[0x02], &[0x01, 0x94]),
+        ]);
+
+        block_on(sensor.set_limit(Limit::Critical, temp(85.75))).unwrap();
+        assert_eq!(block_on(sensor.limit(Limit::Upper)).unwrap(), temp(25.25));
+        // Unrepresentable limits never reach the bus.
+        assert!(block_on(sensor.set_limit(Limit::Lower, temp(10.1))).is_err());
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn resolution() {
+        let mut sensor = new_sensor(&[
+            Transaction::write(ADDRESS, &[0x08, 0x00]),
+            Transaction::write_read(ADDRESS, &[0x08], &[0x03]),
+        ]);
+
+        block_on(sensor.set_resolution(Resolution::Half, ms(0.0))).unwrap();
+        assert_eq!(sensor.resolution(), Resolution::Half);
+        assert_eq!(
+            block_on(sensor.read_resolution()).unwrap(),
+            Resolution::Sixteenth
+        );
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn readings_wait_for_conversion() {
+        let transaction = Transaction::write_read(ADDRESS, &[0x05], &[0x01, 0x94]);
+        let mut sensor = new_sensor(&[transaction.clone(), transaction.clone(), transaction]);
+
+        block_on(sensor.reading(ms(0.0))).unwrap();
+        assert_eq!(sensor.delay.elapsed_ns, 0);
+        // Too soon for a 0.0625°C conversion, so the reading waits until it's done.
+        block_on(sensor.reading(ms(100.0))).unwrap();
+        assert_eq!(sensor.delay.elapsed_ns, 150_000_000);
+        block_on(sensor.reading(ms(600.0))).unwrap();
+        assert_eq!(sensor.delay.elapsed_ns, 150_000_000);
+        sensor.i2c.done();
+    }
+
+    #[test]
+    fn discovers_sensors() {
+        let no_ack = i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Address);
+        let absent =
+            |address| Transaction::write_read(address, &[0x06], &[0x00, 0x00]).with_error(no_ack);
+        let bus = Bus::new(&[
+            Transaction::write_read(0x18, &[0x06], &[0x00, 0x54]),
+            Transaction::write_read(0x18, &[0x07], &[0x04, 0x00]),
+            Transaction::write_read(0x18, &[0x08], &[0x03]),
+            absent(0x19),
+            // Another device strapped into the range.
+            Transaction::write_read(0x1A, &[0x06], &[0x12, 0x34]),
+            absent(0x1B),
+            absent(0x1C),
+            Transaction::write_read(0x1D, &[0x06], &[0x00, 0x54]),
+            Transaction::write_read(0x1D, &[0x07], &[0x04, 0x01]),
+            Transaction::write_read(0x1D, &[0x08], &[0x00]),
+            // Same manufacturer, different part.
+            Transaction::write_read(0x1E, &[0x06], &[0x00, 0x54]),
+            Transaction::write_read(0x1E, &[0x07], &[0x08, 0x00]),
+            absent(0x1F),
+        ]);
+
+        let sensors = block_on(discover(|address| {
+            Mcp9808::new(bus.clone(), Delay::default(), address)
+        }));
+        let addresses: Vec<_, MAX_SENSORS> = sensors.iter().map(Mcp9808::address).collect();
+        assert_eq!(
+            addresses,
+            [Address::new(0x18).unwrap(), Address::new(0x1D).unwrap()]
+        );
+        let resolutions: Vec<_, MAX_SENSORS> = sensors.iter().map(Mcp9808::resolution).collect();
+        assert_eq!(resolutions, [Resolution::Sixteenth, Resolution::Half]);
+        bus.done();
+    }
+
+    #[test]
+    fn bus_error() {
+        let no_ack = i2c::ErrorKind::NoAcknowledge(i2c::NoAcknowledgeSource::Address);
+        let mut sensor = new_sensor(&[
+            Transaction::write_read(ADDRESS, &[0x05], &[0x00, 0x00]).with_error(no_ack)
+        ]);
+
+        assert_eq!(block_on(sensor.temp(ms(0.0))), Err(Error::I2CError(no_ack)));
+        sensor.i2c.done();
+    }
+}
@@ -0,0 +1,115 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use embedded_hal_async::{
+    delay::DelayNs,
+    i2c::{ErrorKind, ErrorType, I2c, Operation},
+};
+
+/// Represents an I2C transaction expected by [`Bus`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    address: u8,
+    write: Vec<u8>,
+    read: Vec<u8>,
+    error: Option<ErrorKind>,
+}
+
+impl Transaction {
+    pub fn write(address: u8, bytes: &[u8]) -> Self {
+        Self {
+            address,
+            write: bytes.to_vec(),
+            read: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn write_read(address: u8, bytes: &[u8], response: &[u8]) -> Self {
+        Self {
+            address,
+            write: bytes.to_vec(),
+            read: response.to_vec(),
+            error: None,
+        }
+    }
+
+    /// Fails the transaction with `error` once it's matched.
+    pub fn with_error(mut self, error: ErrorKind) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+/// Represents an I2C bus that replays register transactions.
+///
+/// Panics on any transaction other than the next expected one. Clones share the expected
+/// transactions, like devices on one bus.
+#[derive(Debug, Clone)]
+pub struct Bus {
+    expected: Rc<RefCell<VecDeque<Transaction>>>,
+}
+
+impl Bus {
+    pub fn new(expected: &[Transaction]) -> Self {
+        Self {
+            expected: Rc::new(RefCell::new(expected.iter().cloned().collect())),
+        }
+    }
+
+    /// Panics if any expected transaction didn't happen.
+    pub fn done(&self) {
+        let expected = self.expected.borrow();
+        assert!(expected.is_empty(), "missing transactions: {expected:02X?}");
+    }
+}
+
+impl ErrorType for Bus {
+    type Error = ErrorKind;
+}
+
+impl I2c for Bus {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let expected = self
+            .expected
+            .borrow_mut()
+            .pop_front()
+            .expect("unexpected transaction");
+        assert_eq!(address, expected.address, "wrong address");
+
+        let mut written = Vec::new();
+        let mut response = expected.read.iter();
+        for operation in operations {
+            match operation {
+                Operation::Write(bytes) => written.extend_from_slice(bytes),
+                Operation::Read(buffer) => {
+                    for byte in buffer.iter_mut() {
+                        *byte = *response.next().expect("read past the response");
+                    }
+                }
+            }
+        }
+        assert_eq!(written, expected.write, "wrong bytes written");
+        assert_eq!(response.len(), 0, "response not fully read");
+
+        match expected.error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Represents a delay that returns immediately and records how long it was asked to wait.
+#[derive(Debug, Default)]
+pub struct Delay {
+    pub elapsed_ns: u64,
+}
+
+impl DelayNs for Delay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.elapsed_ns += u64::from(ns);
+    }
+}
@@ -0,0 +1,3 @@
+pub mod mcp9808;
+#[cfg(test)]
+mod mock;
@@ -0,0 +1,23 @@
+//! Fixtures shared by the unit tests.
+
+use uom::si::{
+    angular_velocity::revolution_per_minute, ratio::percent,
+    thermodynamic_temperature::degree_celsius,
+};
+
+use crate::{
+    decode::fan,
+    units::{AngularVelocity, Ratio, ThermodynamicTemperature},
+};
+
+pub fn speed(speed: f64) -> fan::Speed {
+    fan::Speed::new(Ratio::new::<percent>(speed)).unwrap()
+}
+
+pub fn temp(temp: f64) -> ThermodynamicTemperature {
+    ThermodynamicTemperature::new::<degree_celsius>(temp)
+}
+
+pub fn rpm(rpm: f64) -> AngularVelocity {
+    AngularVelocity::new::<revolution_per_minute>(rpm)
+}
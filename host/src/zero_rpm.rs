@@ -0,0 +1,358 @@
+use core::cmp::Ordering;
+
+use uom::si::{ratio::percent, thermodynamic_temperature::degree_celsius, time::second};
+
+use crate::{
+    decode::fan,
+    units::{Ratio, ThermodynamicTemperature, Time},
+};
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Represents a zero RPM policy error.
+#[derive(Debug, PartialEq, thiserror::Error, defmt::Format)]
+pub enum Error {
+    /// The start-kick duration is negative.
+    #[error("invalid kick duration: expected x≥0s, got {0}s")]
+    InvalidKickDuration(f64),
+    /// Only one of the stop and start temperatures is set.
+    #[error("missing zero rpm threshold: the stop and start temperatures must be set together")]
+    MissingThreshold,
+    /// The start temperature isn't above the stop temperature.
+    #[error("invalid zero rpm thresholds: expected stop<start, got {0}°C..{1}°C")]
+    InvalidThresholds(f64, f64),
+    /// A fan decode error occurred.
+    #[error("fan decode error: {0}")]
+    FanDecodeError(#[from] fan::Error),
+}
+
+/// Represents zero RPM policy parameters.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Config {
+    /// The fan stops below this temperature. `None` never stops the fan on temperature alone.
+    pub stop_below: Option<ThermodynamicTemperature>,
+    /// A stopped fan restarts above this temperature. Must be above `stop_below`, so a
+    /// temperature hovering around either threshold doesn't start and stop the fan repeatedly.
+    pub start_above: Option<ThermodynamicTemperature>,
+    /// The slowest speed the fan reliably keeps spinning at. Slower requested speeds are raised
+    /// to this, except zero which stops the fan.
+    pub min_running_speed: fan::Speed,
+    /// The speed used to get a stopped fan spinning.
+    pub kick_speed: fan::Speed,
+    /// How long to hold the kick speed after the fan starts.
+    pub kick_duration: Time,
+}
+
+impl Default for Config {
+    /// Creates a policy that never stops the fan and never kicks it.
+    fn default() -> Self {
+        Self {
+            stop_below: None,
+            start_above: None,
+            min_running_speed: fan::Speed::default(),
+            kick_speed: fan::Speed::default(),
+            kick_duration: Time::default(),
+        }
+    }
+}
+
+/// Represents whether the fan is stopped, starting, or running.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum State {
+    Stopped,
+    Kicking { until: Time },
+    Running,
+}
+
+/// Represents a policy for fans that stall at low duty cycles.
+///
+/// Lets the fan stop entirely below a temperature and restart above a higher one, keeps a running
+/// fan at or above its minimum spinning speed, and briefly drives a stopped fan hard whenever it
+/// has to start.
+#[derive(Debug, Clone)]
+pub struct ZeroRpm {
+    config: Config,
+    state: State,
+}
+
+impl ZeroRpm {
+    pub fn new(config: Config) -> Result<Self> {
+        let kick_duration = config.kick_duration.get::<second>();
+        if kick_duration.is_nan() || kick_duration < 0.0 {
+            return Err(Error::InvalidKickDuration(kick_duration));
+        }
+
+        match (config.stop_below, config.start_above) {
+            (Some(stop_below), Some(start_above)) => {
+                let (stop_below, start_above) = (
+                    stop_below.get::<degree_celsius>(),
+                    start_above.get::<degree_celsius>(),
+                );
+                if start_above.partial_cmp(&stop_below) != Some(Ordering::Greater) {
+                    return Err(Error::InvalidThresholds(stop_below, start_above));
+                }
+            }
+            (None, None) => {}
+            _ => return Err(Error::MissingThreshold),
+        }
+
+        Ok(Self {
+            config,
+            state: State::Stopped,
+        })
+    }
+
+    /// Returns `true` while the fan is being kicked.
+    #[must_use]
+    pub fn is_kicking(&self) -> bool {
+        matches!(self.state, State::Kicking { .. })
+    }
+
+    /// Returns `true` while the fan is stopped.
+    #[must_use]
+    pub fn is_stopped(&self) -> bool {
+        self.state == State::Stopped
+    }
+
+    /// Sets the slowest speed the fan reliably keeps spinning at, e.g. from a measured
+    /// calibration. The kick speed is raised to match if it's slower.
+    pub fn set_min_running_speed(&mut self, min_running_speed: fan::Speed) {
+        self.config.min_running_speed = min_running_speed;
+        self.config.kick_speed = Self::max(self.config.kick_speed, min_running_speed);
+    }
+
+    /// Returns the fan speed to apply at `now`.
+    ///
+    /// `target` is the fan speed the curve or controller wants at `temp`. A running fan stops below
+    /// the stop temperature, and a stopped fan stays stopped until the start temperature is
+    /// exceeded. An unknown temperature never stops the fan.
+    pub fn update(
+        &mut self,
+        now: Time,
+        temp: Option<ThermodynamicTemperature>,
+        target: fan::Speed,
+    ) -> Result<fan::Speed> {
+        let too_cold = match (temp, self.config.stop_below, self.config.start_above) {
+            (Some(temp), Some(stop_below), Some(start_above)) => {
+                let temp = temp.get::<degree_celsius>();
+                if self.state == State::Stopped {
+                    temp <= start_above.get::<degree_celsius>()
+                } else {
+                    temp < stop_below.get::<degree_celsius>()
+                }
+            }
+            _ => false,
+        };
+        let stop = too_cold || target.get::<percent>() <= 0.0;
+
+        if stop {
+            self.state = State::Stopped;
+            return Ok(fan::Speed::new(Ratio::new::<percent>(0.0))?);
+        }
+
+        if self.state == State::Stopped {
+            self.state = State::Kicking {
+                until: now + self.config.kick_duration,
+            };
+        }
+
+        if let State::Kicking { until } = self.state {
+            if now < until {
+                return Ok(Self::max(target, self.config.kick_speed));
+            }
+            self.state = State::Running;
+        }
+
+        Ok(Self::max(target, self.config.min_running_speed))
+    }
+
+    fn max(a: fan::Speed, b: fan::Speed) -> fan::Speed {
+        if a >= b {
+            a
+        } else {
+            b
+        }
+    }
+}
+
+impl Default for ZeroRpm {
+    fn default() -> Self {
+        Self::new(Config::default()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::speed;
+
+    fn update(zero_rpm: &mut ZeroRpm, now: f64, temp: f64, target: f64) -> fan::Speed {
+        zero_rpm
+            .update(
+                Time::new::<second>(now),
+                Some(ThermodynamicTemperature::new::<degree_celsius>(temp)),
+                speed(target),
+            )
+            .unwrap()
+    }
+
+    fn new_zero_rpm() -> ZeroRpm {
+        ZeroRpm::new(Config {
+            stop_below: Some(ThermodynamicTemperature::new::<degree_celsius>(30.0)),
+            start_above: Some(ThermodynamicTemperature::new::<degree_celsius>(32.0)),
+            min_running_speed: speed(20.0),
+            kick_speed: speed(100.0),
+            kick_duration: Time::new::<second>(1.0),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn invalid_kick_duration() {
+        let config = Config {
+            kick_duration: Time::new::<second>(-1.0),
+            ..Config::default()
+        };
+        assert_eq!(
+            ZeroRpm::new(config).unwrap_err(),
+            Error::InvalidKickDuration(-1.0)
+        );
+    }
+
+    #[test]
+    fn invalid_thresholds() {
+        let temp = ThermodynamicTemperature::new::<degree_celsius>;
+        let config = Config {
+            stop_below: Some(temp(30.0)),
+            start_above: Some(temp(30.0)),
+            ..Config::default()
+        };
+        assert_eq!(
+            ZeroRpm::new(config).unwrap_err(),
+            Error::InvalidThresholds(30.0, 30.0)
+        );
+
+        let config = Config {
+            stop_below: Some(temp(30.0)),
+            ..Config::default()
+        };
+        assert_eq!(ZeroRpm::new(config).unwrap_err(), Error::MissingThreshold);
+    }
+
+    #[test]
+    fn stops_below_temperature() {
+        let mut zero_rpm = new_zero_rpm();
+
+        assert_eq!(update(&mut zero_rpm, 0.0, 29.9, 25.0), speed(0.0));
+        assert!(zero_rpm.is_stopped());
+    }
+
+    #[test]
+    fn restarts_above_start_temperature() {
+        let mut zero_rpm = new_zero_rpm();
+
+        update(&mut zero_rpm, 0.0, 25.0, 25.0);
+        assert_eq!(update(&mut zero_rpm, 1.0, 31.0, 25.0), speed(0.0));
+        assert_eq!(update(&mut zero_rpm, 2.0, 32.0, 25.0), speed(0.0));
+        assert_eq!(update(&mut zero_rpm, 3.0, 32.1, 25.0), speed(100.0));
+    }
+
+    #[test]
+    fn does_not_hunt_around_threshold() {
+        let mut zero_rpm = new_zero_rpm();
+
+        // Running fans keep running through the deadband...
+        update(&mut zero_rpm, 0.0, 35.0, 25.0);
+        for now in 1..10 {
+            let temp = if now % 2 == 0 { 30.1 } else { 31.9 };
+            assert_eq!(
+                update(&mut zero_rpm, f64::from(now), temp, 25.0),
+                speed(25.0)
+            );
+        }
+
+        // ...and stopped fans stay stopped while the temperature oscillates across the stop
+        // temperature.
+        for now in 10..20 {
+            let temp = if now % 2 == 0 { 29.9 } else { 30.1 };
+            assert_eq!(
+                update(&mut zero_rpm, f64::from(now), temp, 25.0),
+                speed(0.0)
+            );
+            assert!(zero_rpm.is_stopped());
+        }
+    }
+
+    #[test]
+    fn stops_at_zero_speed() {
+        let mut zero_rpm = new_zero_rpm();
+
+        assert_eq!(update(&mut zero_rpm, 0.0, 40.0, 0.0), speed(0.0));
+        assert!(zero_rpm.is_stopped());
+    }
+
+    #[test]
+    fn kicks_when_starting() {
+        let mut zero_rpm = new_zero_rpm();
+
+        assert_eq!(update(&mut zero_rpm, 0.0, 35.0, 30.0), speed(100.0));
+        assert!(zero_rpm.is_kicking());
+        assert_eq!(update(&mut zero_rpm, 0.5, 35.0, 30.0), speed(100.0));
+        assert_eq!(update(&mut zero_rpm, 1.0, 35.0, 30.0), speed(30.0));
+        assert!(!zero_rpm.is_kicking());
+    }
+
+    #[test]
+    fn kicks_again_after_stopping() {
+        let mut zero_rpm = new_zero_rpm();
+
+        update(&mut zero_rpm, 0.0, 35.0, 30.0);
+        update(&mut zero_rpm, 2.0, 35.0, 30.0);
+        assert_eq!(update(&mut zero_rpm, 3.0, 25.0, 10.0), speed(0.0));
+        assert_eq!(update(&mut zero_rpm, 4.0, 35.0, 30.0), speed(100.0));
+    }
+
+    #[test]
+    fn enforces_min_running_speed() {
+        let mut zero_rpm = new_zero_rpm();
+
+        update(&mut zero_rpm, 0.0, 35.0, 30.0);
+        assert_eq!(update(&mut zero_rpm, 2.0, 35.0, 5.0), speed(20.0));
+        assert_eq!(update(&mut zero_rpm, 3.0, 35.0, 50.0), speed(50.0));
+    }
+
+    #[test]
+    fn sets_min_running_speed() {
+        let mut zero_rpm = ZeroRpm::new(Config {
+            kick_speed: speed(30.0),
+            kick_duration: Time::new::<second>(1.0),
+            ..Config::default()
+        })
+        .unwrap();
+        zero_rpm.set_min_running_speed(speed(40.0));
+
+        assert_eq!(update(&mut zero_rpm, 0.0, 35.0, 10.0), speed(40.0));
+        assert_eq!(update(&mut zero_rpm, 2.0, 35.0, 5.0), speed(40.0));
+        assert_eq!(update(&mut zero_rpm, 3.0, 35.0, 50.0), speed(50.0));
+    }
+
+    #[test]
+    fn unknown_temperature_does_not_stop() {
+        let mut zero_rpm = new_zero_rpm();
+
+        update(&mut zero_rpm, 0.0, 35.0, 30.0);
+        let fan_speed = zero_rpm
+            .update(Time::new::<second>(2.0), None, speed(30.0))
+            .unwrap();
+        assert_eq!(fan_speed, speed(30.0));
+    }
+
+    #[test]
+    fn default_passes_through() {
+        let mut zero_rpm = ZeroRpm::default();
+
+        assert_eq!(update(&mut zero_rpm, 0.0, 0.0, 5.0), speed(5.0));
+        assert_eq!(update(&mut zero_rpm, 1.0, 0.0, 0.0), speed(0.0));
+        assert_eq!(update(&mut zero_rpm, 2.0, 0.0, 5.0), speed(5.0));
+    }
+}